@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::damage::{DamageDealtEvent, Health};
+use crate::enemy::{Enemy, EnemyKind};
+use crate::player::Player;
+use crate::state::GameState;
+use crate::*;
+
+pub struct AdaptiveDirectorPlugin;
+
+/// Tunes the scripted waves to keep the player's intensity inside a target
+/// band. Every modifier stays neutral while disabled in `Settings`.
+#[derive(Resource)]
+pub struct AdaptiveDirector {
+    /// Estimated stress of the player, 0.0 is bored and 1.0 is overwhelmed.
+    pub intensity: f32,
+    /// Multiplies the enemy count of every wave spawn.
+    pub spawn_scale: f32,
+    /// Chance for each spawned enemy to be swapped for a tougher kind.
+    pub upgrade_chance: f32,
+    /// Multiplies pickup drop chances.
+    pub drop_scale: f32,
+    /// Smoothed enemy kills per second.
+    pub kill_rate: f32,
+    pub since_last_hit: f32,
+    kills: u32,
+    eval_timer: Timer,
+}
+
+impl Plugin for AdaptiveDirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AdaptiveDirector::default())
+            .add_systems(OnEnter(GameState::GameInit), reset_adaptive_director)
+            .add_systems(
+                Update,
+                (track_player_performance, update_adaptive_director)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+impl Default for AdaptiveDirector {
+    fn default() -> Self {
+        Self {
+            intensity: 0.0,
+            spawn_scale: 1.0,
+            upgrade_chance: 0.0,
+            drop_scale: 1.0,
+            kill_rate: 0.0,
+            since_last_hit: 0.0,
+            kills: 0,
+            eval_timer: Timer::from_seconds(ADAPTIVE_EVAL_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+impl AdaptiveDirector {
+    /// Rolls `upgrade_chance` to pick the kind that actually spawns.
+    pub fn pick_kind(&self, kind: EnemyKind, rng: &mut impl Rng) -> EnemyKind {
+        if self.upgrade_chance > 0.0 && rng.gen::<f32>() < self.upgrade_chance {
+            return tougher_kind(kind);
+        }
+
+        kind
+    }
+}
+
+fn tougher_kind(kind: EnemyKind) -> EnemyKind {
+    match kind {
+        EnemyKind::Swarmer => EnemyKind::Runner,
+        EnemyKind::Runner => EnemyKind::Grunt,
+        EnemyKind::Grunt => EnemyKind::Spitter,
+        EnemyKind::Spitter | EnemyKind::Tank => EnemyKind::Tank,
+        EnemyKind::Boss => EnemyKind::Boss,
+    }
+}
+
+fn reset_adaptive_director(mut director: ResMut<AdaptiveDirector>) {
+    *director = AdaptiveDirector::default();
+}
+
+fn track_player_performance(
+    time: Res<Time>,
+    mut director: ResMut<AdaptiveDirector>,
+    mut events: EventReader<DamageDealtEvent>,
+    player_query: Query<(), With<Player>>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    director.since_last_hit += time.delta_seconds();
+    for event in events.read() {
+        if player_query.contains(event.target) {
+            director.intensity += event.amount / PLAYER_HEALTH * ADAPTIVE_HIT_INTENSITY;
+            director.since_last_hit = 0.0;
+        } else if event.killed && enemy_query.contains(event.target) {
+            director.kills += 1;
+        }
+    }
+
+    // like a peak, intensity holds for a moment after a hit before relaxing
+    if director.since_last_hit > ADAPTIVE_HIT_HOLD_SECS {
+        director.intensity -= ADAPTIVE_INTENSITY_DECAY * time.delta_seconds();
+    }
+    director.intensity = director.intensity.clamp(0.0, 1.0);
+}
+
+fn update_adaptive_director(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut director: ResMut<AdaptiveDirector>,
+    player_query: Query<&Health, With<Player>>,
+) {
+    if player_query.is_empty() || !director.eval_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let kill_rate = director.kills as f32 / ADAPTIVE_EVAL_INTERVAL;
+    director.kill_rate = director.kill_rate.lerp(kill_rate, 0.5);
+    director.kills = 0;
+
+    if !settings.adaptive_director {
+        let intensity = director.intensity;
+        let kill_rate = director.kill_rate;
+        let since_last_hit = director.since_last_hit;
+        *director = AdaptiveDirector {
+            intensity,
+            kill_rate,
+            since_last_hit,
+            ..default()
+        };
+        return;
+    }
+
+    // low health keeps intensity up even when the player hasn't been hit lately
+    let health = (player_query.single().0 / PLAYER_HEALTH).clamp(0.0, 1.0);
+    let intensity = director.intensity.max((1.0 - health) * 0.8);
+    let dominating = director.kill_rate >= ADAPTIVE_DOMINATING_KILL_RATE
+        || director.since_last_hit >= ADAPTIVE_DOMINATING_SECS;
+
+    let previous_scale = director.spawn_scale;
+    let decision = if intensity > ADAPTIVE_TARGET_HIGH {
+        director.spawn_scale /= ADAPTIVE_STEP;
+        director.upgrade_chance -= ADAPTIVE_UPGRADE_STEP;
+        director.drop_scale *= ADAPTIVE_STEP;
+        "easing off"
+    } else if intensity < ADAPTIVE_TARGET_LOW && dominating {
+        director.spawn_scale *= ADAPTIVE_STEP;
+        director.upgrade_chance += ADAPTIVE_UPGRADE_STEP;
+        director.drop_scale /= ADAPTIVE_STEP;
+        "ramping up"
+    } else {
+        return;
+    };
+    director.spawn_scale = director
+        .spawn_scale
+        .clamp(ADAPTIVE_MIN_SPAWN_SCALE, ADAPTIVE_MAX_SPAWN_SCALE);
+    director.upgrade_chance = director
+        .upgrade_chance
+        .clamp(0.0, ADAPTIVE_MAX_UPGRADE_CHANCE);
+    director.drop_scale = director.drop_scale.clamp(
+        1.0 / ADAPTIVE_MAX_SPAWN_SCALE,
+        1.0 / ADAPTIVE_MIN_SPAWN_SCALE,
+    );
+
+    if director.spawn_scale != previous_scale {
+        info!(
+            "director {decision}: intensity {intensity:.2}, health {:.0}%, {:.1} kills/s, {:.0}s since hit -> spawns x{:.2}, upgrades {:.0}%, drops x{:.2}",
+            health * 100.0,
+            director.kill_rate,
+            director.since_last_hit,
+            director.spawn_scale,
+            director.upgrade_chance * 100.0,
+            director.drop_scale,
+        );
+    }
+}
@@ -1,16 +1,26 @@
 use bevy::prelude::*;
 
+use crate::damage::{DamageDealtEvent, Invulnerable};
 use crate::enemy::Enemy;
 use crate::player::{Player, PlayerState};
 use crate::state::GameState;
-use crate::weapon::Weapon;
-use crate::{CursorPosition, SPRITE_SHEET_WIDTH};
+use crate::weapon::ActiveWeapon;
+use crate::world::wrapped_delta;
+use crate::{
+    CursorPosition, Settings, ENEMY_HIT_FLASH_SECS, PLAYER_FLASH_INTERVAL, SPRITE_SHEET_WIDTH,
+};
 
 pub struct AnimationPlugin;
 
 #[derive(Component, Deref, DerefMut)]
 pub struct AnimationTimer(pub Timer);
 
+#[derive(Component, Deref, DerefMut)]
+pub struct HitFlash(pub Timer);
+
+// tinting far past white saturates every non-transparent pixel to white
+const HIT_FLASH_COLOR: Color = Color::srgb(10.0, 10.0, 10.0);
+
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
@@ -22,6 +32,9 @@ impl Plugin for AnimationPlugin {
                 flip_player_sprite_x,
                 flip_weapon_sprite_y,
                 flip_enemy_sprite_x,
+                flash_invulnerable_player,
+                start_enemy_hit_flash,
+                update_enemy_hit_flash,
             )
                 .run_if(in_state(GameState::InGame)),
         );
@@ -54,14 +67,16 @@ fn animate_player(
     }
 }
 
-fn animate_enemy(mut enemy_query: Query<(&mut TextureAtlas, &AnimationTimer), With<Enemy>>) {
+fn animate_enemy(mut enemy_query: Query<(&mut TextureAtlas, &AnimationTimer, &Enemy)>) {
     if enemy_query.is_empty() {
         return;
     }
 
-    for (mut atlas, timer) in enemy_query.iter_mut() {
+    for (mut atlas, timer, enemy) in enemy_query.iter_mut() {
         if timer.just_finished() {
-            atlas.index = 8 + (atlas.index + 1) % SPRITE_SHEET_WIDTH as usize;
+            let stats = enemy.kind.stats();
+            let frame = atlas.index.saturating_sub(stats.first_frame) + 1;
+            atlas.index = stats.first_frame + frame % stats.frame_count;
         }
     }
 }
@@ -82,6 +97,7 @@ fn flip_player_sprite_x(
 }
 
 fn flip_enemy_sprite_x(
+    settings: Res<Settings>,
     player_query: Query<&Transform, With<Player>>,
     mut enemy_query: Query<(&mut Sprite, &Transform), With<Enemy>>,
 ) {
@@ -89,15 +105,16 @@ fn flip_enemy_sprite_x(
         return;
     }
 
-    let player_pos = player_query.single().translation;
+    let player_pos = player_query.single().translation.truncate();
     for (mut sprite, transform) in enemy_query.iter_mut() {
-        sprite.flip_x = player_pos.x < transform.translation.x;
+        let pos = transform.translation.truncate();
+        sprite.flip_x = wrapped_delta(pos, player_pos, settings.wraps_world()).x < 0.0;
     }
 }
 
 fn flip_weapon_sprite_y(
     cursor_position: Res<CursorPosition>,
-    mut weapon_query: Query<(&mut Sprite, &Transform), With<Weapon>>,
+    mut weapon_query: Query<(&mut Sprite, &Transform), With<ActiveWeapon>>,
 ) {
     if weapon_query.is_empty() {
         return;
@@ -110,3 +127,51 @@ fn flip_weapon_sprite_y(
         sprite.flip_y = cursor_position.x < transform.translation.x;
     }
 }
+
+fn flash_invulnerable_player(
+    mut player_query: Query<(&mut Sprite, Option<&Invulnerable>), With<Player>>,
+) {
+    if player_query.is_empty() {
+        return;
+    }
+
+    let (mut sprite, invulnerable) = player_query.single_mut();
+    let flash_frame =
+        invulnerable.map(|timer| (timer.elapsed_secs() / PLAYER_FLASH_INTERVAL) as u32);
+    let alpha = match flash_frame {
+        Some(frame) if frame.is_multiple_of(2) => 0.3,
+        _ => 1.0,
+    };
+    sprite.color.set_alpha(alpha);
+}
+
+fn start_enemy_hit_flash(
+    mut commands: Commands,
+    mut events: EventReader<DamageDealtEvent>,
+    mut enemy_query: Query<&mut Sprite, With<Enemy>>,
+) {
+    for event in events.read() {
+        if let Ok(mut sprite) = enemy_query.get_mut(event.target) {
+            sprite.color = HIT_FLASH_COLOR;
+            commands
+                .entity(event.target)
+                .try_insert(HitFlash(Timer::from_seconds(
+                    ENEMY_HIT_FLASH_SECS,
+                    TimerMode::Once,
+                )));
+        }
+    }
+}
+
+fn update_enemy_hit_flash(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut enemy_query: Query<(Entity, &mut Sprite, &mut HitFlash, &Enemy)>,
+) {
+    for (entity, mut sprite, mut flash, enemy) in enemy_query.iter_mut() {
+        if flash.tick(time.delta()).finished() {
+            sprite.color = enemy.kind.stats().color;
+            commands.entity(entity).remove::<HitFlash>();
+        }
+    }
+}
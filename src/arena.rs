@@ -0,0 +1,432 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::director::WaveSchedule;
+use crate::world::{ground_image, GameEntity, Obstacle};
+use crate::worldgen::{Biome, ObstacleKind};
+use crate::*;
+
+pub struct ArenaPlugin;
+
+/// A hand-authored map, loaded from a `.arena.ron` file.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct Arena {
+    /// Format version the file was written for, see `ARENA_FORMAT_VERSION`.
+    pub version: u32,
+    pub name: String,
+    /// World units covered by each character of `tiles`.
+    pub tile_size: f32,
+    /// Rows of tile characters, top row first, centred on the origin.
+    pub tiles: Vec<String>,
+    pub palette: HashMap<char, ArenaTile>,
+    #[serde(default)]
+    pub obstacles: Vec<ArenaObstacle>,
+    /// Areas scattered wave spawns arrive in. Empty spawns them around the
+    /// player like the generated world does.
+    #[serde(default)]
+    pub spawn_zones: Vec<SpawnZone>,
+    pub player_start: (f32, f32),
+    /// Path of the `.waves.ron` schedule to play, the default one when unset.
+    #[serde(default)]
+    pub waves: Option<String>,
+    #[serde(skip)]
+    pub wave_schedule: Option<Handle<WaveSchedule>>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum ArenaTile {
+    Ground(Biome),
+    /// Blocks movement and bullets, drawn as an obstacle of this kind.
+    Solid(ObstacleKind),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ArenaObstacle {
+    pub pos: (f32, f32),
+    pub size: (f32, f32),
+    pub kind: ObstacleKind,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct SpawnZone {
+    pub center: (f32, f32),
+    pub radius: f32,
+}
+
+#[derive(Default)]
+pub struct ArenaLoader;
+
+#[derive(Debug, Error)]
+pub enum ArenaLoaderError {
+    #[error("could not read arena: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse arena: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("unsupported arena version {0}, expected {ARENA_FORMAT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("tile '{0}' is missing from the palette")]
+    UnknownTile(char),
+    #[error("row {row} has {found} tiles, expected {expected}")]
+    RaggedRow {
+        row: usize,
+        found: usize,
+        expected: usize,
+    },
+    #[error("tile size must be positive, got {0}")]
+    InvalidTileSize(f32),
+    #[error("tile layout doesn't fit in the world")]
+    LayoutOutsideWorld,
+    #[error("player start is outside the world")]
+    PlayerStartOutsideWorld,
+    #[error("spawn zone {0} is outside the world")]
+    SpawnZoneOutsideWorld(usize),
+    #[error("spawn zone {index} has an invalid radius {radius}")]
+    InvalidSpawnZoneRadius { index: usize, radius: f32 },
+    #[error("obstacle {index} has an invalid size {size:?}")]
+    InvalidObstacleSize { index: usize, size: (f32, f32) },
+    #[error("obstacle {0} is outside the world")]
+    ObstacleOutsideWorld(usize),
+}
+
+impl Plugin for ArenaPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Arena>().init_asset_loader::<ArenaLoader>();
+    }
+}
+
+impl AssetLoader for ArenaLoader {
+    type Asset = Arena;
+    type Settings = ();
+    type Error = ArenaLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Arena, ArenaLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut arena = Arena::parse(&bytes)?;
+        arena.wave_schedule = arena.waves.as_ref().map(|path| load_context.load(path));
+        Ok(arena)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arena.ron"]
+    }
+}
+
+impl Arena {
+    /// Reads an arena and checks it describes a map that fits in the world.
+    fn parse(bytes: &[u8]) -> Result<Arena, ArenaLoaderError> {
+        let arena: Arena = ron::de::from_bytes(bytes)?;
+        if arena.version != ARENA_FORMAT_VERSION {
+            return Err(ArenaLoaderError::UnsupportedVersion(arena.version));
+        }
+        if let Some(tile) = arena
+            .tiles
+            .iter()
+            .flat_map(|row| row.chars())
+            .find(|tile| !arena.palette.contains_key(tile))
+        {
+            return Err(ArenaLoaderError::UnknownTile(tile));
+        }
+        let expected = arena.columns();
+        for (row, tiles) in arena.tiles.iter().enumerate() {
+            let found = tiles.chars().count();
+            if found != expected {
+                return Err(ArenaLoaderError::RaggedRow {
+                    row,
+                    found,
+                    expected,
+                });
+            }
+        }
+        if arena.tile_size.is_nan() || arena.tile_size <= 0.0 {
+            return Err(ArenaLoaderError::InvalidTileSize(arena.tile_size));
+        }
+
+        let world = Rect::from_center_half_size(Vec2::ZERO, vec2(WORLD_WIDTH, WORLD_HEIGHT));
+        let origin = arena.origin();
+        if !world.contains(origin) || !world.contains(-origin) {
+            return Err(ArenaLoaderError::LayoutOutsideWorld);
+        }
+        if !world.contains(arena.player_start()) {
+            return Err(ArenaLoaderError::PlayerStartOutsideWorld);
+        }
+        for (index, zone) in arena.spawn_zones.iter().enumerate() {
+            if zone.radius.is_nan() || zone.radius < 0.0 {
+                return Err(ArenaLoaderError::InvalidSpawnZoneRadius {
+                    index,
+                    radius: zone.radius,
+                });
+            }
+            let bounds = Rect::from_center_half_size(zone.center(), Vec2::splat(zone.radius));
+            if !world.contains(bounds.min) || !world.contains(bounds.max) {
+                return Err(ArenaLoaderError::SpawnZoneOutsideWorld(index));
+            }
+        }
+        for (index, obstacle) in arena.obstacles.iter().enumerate() {
+            let size = vec2(obstacle.size.0, obstacle.size.1);
+            if size.is_nan() || size.cmple(Vec2::ZERO).any() {
+                return Err(ArenaLoaderError::InvalidObstacleSize {
+                    index,
+                    size: obstacle.size,
+                });
+            }
+            let bounds = Rect::from_center_size(vec2(obstacle.pos.0, obstacle.pos.1), size);
+            if !world.contains(bounds.min) || !world.contains(bounds.max) {
+                return Err(ArenaLoaderError::ObstacleOutsideWorld(index));
+            }
+        }
+
+        Ok(arena)
+    }
+
+    pub fn player_start(&self) -> Vec2 {
+        vec2(self.player_start.0, self.player_start.1)
+    }
+
+    fn columns(&self) -> usize {
+        self.tiles
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Bottom left corner of the tile layout.
+    fn origin(&self) -> Vec2 {
+        -vec2(self.columns() as f32, self.tiles.len() as f32) * self.tile_size / 2.0
+    }
+
+    fn tile(&self, column: usize, row: usize) -> Option<ArenaTile> {
+        let tile = self.tiles.get(row)?.chars().nth(column)?;
+        self.palette.get(&tile).copied()
+    }
+}
+
+impl SpawnZone {
+    pub fn center(&self) -> Vec2 {
+        vec2(self.center.0, self.center.1)
+    }
+}
+
+/// Spawns the arena's ground, solid tiles and obstacles under one root entity.
+pub fn spawn_arena(commands: &mut Commands, images: &mut Assets<Image>, arena: &Arena) {
+    let (columns, rows) = (arena.columns(), arena.tiles.len());
+    if columns == 0 {
+        return;
+    }
+
+    let mut pixels = Vec::with_capacity(columns * rows * 4);
+    for row in 0..rows {
+        for column in 0..columns {
+            let color = match arena.tile(column, row) {
+                Some(ArenaTile::Ground(biome)) => biome.ground_color(),
+                Some(ArenaTile::Solid(_)) => Biome::Badlands.ground_color(),
+                None => {
+                    pixels.extend([0; 4]);
+                    continue;
+                }
+            };
+            pixels.extend(color.map(|channel| (channel * 255.0) as u8));
+            pixels.push(255);
+        }
+    }
+    let ground = images.add(ground_image(columns as u32, rows as u32, pixels));
+
+    // solid tiles next to each other in a row become one obstacle
+    let origin = arena.origin();
+    let mut solids = Vec::new();
+    for row in 0..rows {
+        let y = origin.y + (rows - row - 1) as f32 * arena.tile_size;
+        let mut column = 0;
+        while column < columns {
+            let Some(ArenaTile::Solid(kind)) = arena.tile(column, row) else {
+                column += 1;
+                continue;
+            };
+            let start = column;
+            while matches!(arena.tile(column, row), Some(ArenaTile::Solid(next)) if next == kind) {
+                column += 1;
+            }
+            let min = vec2(origin.x + start as f32 * arena.tile_size, y);
+            let size = vec2((column - start) as f32, 1.0) * arena.tile_size;
+            solids.push((min + size / 2.0, size, kind));
+        }
+    }
+    let authored = arena.obstacles.iter().map(|obstacle| {
+        (
+            vec2(obstacle.pos.0, obstacle.pos.1),
+            vec2(obstacle.size.0, obstacle.size.1),
+            obstacle.kind,
+        )
+    });
+
+    commands
+        .spawn((SpatialBundle::default(), GameEntity))
+        .with_children(|parent| {
+            parent.spawn(SpriteBundle {
+                texture: ground,
+                sprite: Sprite {
+                    custom_size: Some(vec2(columns as f32, rows as f32) * arena.tile_size),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, -1.0)),
+                ..default()
+            });
+
+            for (pos, size, kind) in solids.into_iter().chain(authored) {
+                parent.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: kind.color(),
+                            custom_size: Some(size),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(pos.extend(0.5)),
+                        ..default()
+                    },
+                    Obstacle {
+                        half_size: size / 2.0,
+                    },
+                ));
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small valid arena with `field` swapped in for the matching line.
+    fn arena_with(field: &str) -> String {
+        let mut fields = [
+            "version: 1",
+            "name: \"Test\"",
+            "tile_size: 100.0",
+            "palette: { '.': Ground(Meadow), '#': Solid(Wall) }",
+            "tiles: [\"#..#\", \"....\"]",
+            "spawn_zones: [(center: (0.0, 500.0), radius: 200.0)]",
+            "obstacles: [(pos: (100.0, 0.0), size: (50.0, 50.0), kind: Rock)]",
+            "player_start: (0.0, 0.0)",
+        ];
+        let key = field.split(':').next().unwrap();
+        if let Some(line) = fields.iter_mut().find(|line| line.starts_with(key)) {
+            *line = field;
+        }
+        format!("({})", fields.join(", "))
+    }
+
+    fn parse(field: &str) -> Result<Arena, ArenaLoaderError> {
+        Arena::parse(arena_with(field).as_bytes())
+    }
+
+    #[test]
+    fn parses_bundled_arena() {
+        let bytes = include_bytes!("../assets/arenas/crossroads.arena.ron");
+        assert!(Arena::parse(bytes).is_ok());
+    }
+
+    #[test]
+    fn parses_valid_arena() {
+        let arena = parse("name: \"Test\"").unwrap();
+        assert_eq!(arena.columns(), 4);
+        assert_eq!(arena.origin(), vec2(-200.0, -100.0));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert!(matches!(
+            parse("version: 2"),
+            Err(ArenaLoaderError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_tiles() {
+        assert!(matches!(
+            parse("tiles: [\"#..#\", \"..x.\"]"),
+            Err(ArenaLoaderError::UnknownTile('x'))
+        ));
+    }
+
+    #[test]
+    fn rejects_ragged_rows() {
+        assert!(matches!(
+            parse("tiles: [\"#..#\", \"..\"]"),
+            Err(ArenaLoaderError::RaggedRow {
+                row: 1,
+                found: 2,
+                expected: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_non_positive_tile_size() {
+        for size in ["0.0", "-10.0", "NaN"] {
+            assert!(matches!(
+                parse(&format!("tile_size: {size}")),
+                Err(ArenaLoaderError::InvalidTileSize(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_anything_outside_the_world() {
+        assert!(matches!(
+            parse("tile_size: 5000.0"),
+            Err(ArenaLoaderError::LayoutOutsideWorld)
+        ));
+        assert!(matches!(
+            parse(&format!("player_start: ({}, 0.0)", WORLD_WIDTH + 1.0)),
+            Err(ArenaLoaderError::PlayerStartOutsideWorld)
+        ));
+        assert!(matches!(
+            parse(&format!(
+                "spawn_zones: [(center: (0.0, 0.0), radius: 10.0), (center: (0.0, {}), radius: 10.0)]",
+                WORLD_HEIGHT
+            )),
+            Err(ArenaLoaderError::SpawnZoneOutsideWorld(1))
+        ));
+        assert!(matches!(
+            parse(&format!(
+                "obstacles: [(pos: ({}, 0.0), size: (50.0, 50.0), kind: Wall)]",
+                WORLD_WIDTH - 10.0
+            )),
+            Err(ArenaLoaderError::ObstacleOutsideWorld(0))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_spawn_zone_radius() {
+        for radius in ["-1.0", "NaN"] {
+            assert!(matches!(
+                parse(&format!(
+                    "spawn_zones: [(center: (0.0, 0.0), radius: {radius})]"
+                )),
+                Err(ArenaLoaderError::InvalidSpawnZoneRadius { index: 0, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_obstacle_size() {
+        for size in ["(0.0, 50.0)", "(50.0, -1.0)", "(NaN, 50.0)"] {
+            assert!(matches!(
+                parse(&format!(
+                    "obstacles: [(pos: (0.0, 0.0), size: {size}, kind: Rock)]"
+                )),
+                Err(ArenaLoaderError::InvalidObstacleSize { index: 0, .. })
+            ));
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::state::GameState;
+use crate::state::{GameState, InRun};
 
 pub struct GameAudioPlugin;
 
@@ -14,14 +14,25 @@ pub struct SoundEffect;
 pub struct GameplayMusic {
     #[dependency]
     handle: Handle<AudioSource>,
+    #[dependency]
+    boss_handle: Handle<AudioSource>,
     entity: Option<Entity>,
 }
 
+/// Swaps the looping track while in game.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MusicChangeEvent {
+    Gameplay,
+    Boss,
+}
+
 impl Plugin for GameAudioPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<GameplayMusic>()
-            .add_systems(OnEnter(GameState::InGame), play_gameplay_music)
-            .add_systems(OnExit(GameState::InGame), stop_music);
+            .add_event::<MusicChangeEvent>()
+            .add_systems(OnEnter(InRun), play_gameplay_music)
+            .add_systems(Update, change_music.run_if(in_state(GameState::InGame)))
+            .add_systems(OnExit(InRun), stop_music);
     }
 }
 
@@ -30,6 +41,7 @@ impl FromWorld for GameplayMusic {
         let assets = world.resource::<AssetServer>();
         Self {
             handle: assets.load("audio/music/davidkbd-temple_of_madness.ogg"),
+            boss_handle: assets.load("audio/music/davidkbd-interstellar.ogg"),
             entity: None,
         }
     }
@@ -49,6 +61,35 @@ fn play_gameplay_music(mut commands: Commands, mut music: ResMut<GameplayMusic>)
     );
 }
 
+fn change_music(
+    mut commands: Commands,
+    mut music: ResMut<GameplayMusic>,
+    mut events: EventReader<MusicChangeEvent>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+
+    if let Some(entity) = music.entity.take() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let source = match event {
+        MusicChangeEvent::Gameplay => music.handle.clone(),
+        MusicChangeEvent::Boss => music.boss_handle.clone(),
+    };
+    music.entity = Some(
+        commands
+            .spawn((
+                AudioBundle {
+                    source,
+                    settings: PlaybackSettings::LOOP,
+                },
+                Music,
+            ))
+            .id(),
+    );
+}
+
 fn stop_music(mut commands: Commands, mut music: ResMut<GameplayMusic>) {
     if let Some(entity) = music.entity.take() {
         commands.entity(entity).despawn_recursive();
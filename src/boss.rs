@@ -0,0 +1,267 @@
+use std::f32::consts::PI;
+
+use bevy::ecs::system::EntityCommands;
+use bevy::math::vec2;
+use bevy::prelude::*;
+
+use crate::audio::MusicChangeEvent;
+use crate::camera::CameraShakeEvent;
+use crate::damage::Health;
+use crate::enemy::{get_random_position_around, spawn_enemy, Enemy, EnemyKind};
+use crate::pattern::PatternPlayer;
+use crate::player::Player;
+use crate::resources::GlobalBulletPatterns;
+use crate::state::{GameState, InRun};
+use crate::world::wrapped_delta;
+use crate::*;
+
+pub struct BossPlugin;
+
+#[derive(Component)]
+pub struct Boss {
+    pub phase: BossPhase,
+    pub phase_timer: Timer,
+    pub attack_timer: Timer,
+    pub charge_dir: Vec2,
+    pub enraged: bool,
+}
+
+/// Time until the next boss shows up. Restarts with every run.
+#[derive(Resource)]
+pub struct BossSpawnTimer(pub Timer);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BossPhase {
+    /// Plays the volley bullet pattern.
+    Volley,
+    /// Repeated wind-up then dash at the player.
+    Charge,
+    /// Calls in rings of minions.
+    Summon,
+}
+
+// fraction of each charge interval spent winding up before the dash
+const CHARGE_WIND_UP: f32 = 0.4;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BossSpawnTimer::default())
+            .add_systems(OnEnter(InRun), reset_boss_spawn_timer)
+            .add_systems(
+                Update,
+                (
+                    spawn_boss,
+                    update_boss_phase,
+                    update_boss_attacks.after(update_boss_phase),
+                    handle_boss_death,
+                )
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+impl Default for BossSpawnTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(
+            BOSS_SPAWN_INTERVAL,
+            TimerMode::Repeating,
+        ))
+    }
+}
+
+impl BossPhase {
+    fn next(self) -> Self {
+        match self {
+            BossPhase::Volley => BossPhase::Charge,
+            BossPhase::Charge => BossPhase::Summon,
+            BossPhase::Summon => BossPhase::Volley,
+        }
+    }
+
+    fn duration(self) -> f32 {
+        match self {
+            BossPhase::Volley => BOSS_VOLLEY_SECS,
+            BossPhase::Charge => BOSS_CHARGE_SECS,
+            BossPhase::Summon => BOSS_SUMMON_SECS,
+        }
+    }
+
+    fn attack_interval(self, enraged: bool) -> f32 {
+        let interval = match self {
+            // volleys are timed by the pattern instead
+            BossPhase::Volley => BOSS_VOLLEY_SECS,
+            BossPhase::Charge => BOSS_CHARGE_INTERVAL,
+            BossPhase::Summon => BOSS_SUMMON_INTERVAL,
+        };
+        if enraged {
+            interval * 0.7
+        } else {
+            interval
+        }
+    }
+}
+
+impl Boss {
+    fn enter_phase(
+        &mut self,
+        phase: BossPhase,
+        entity: &mut EntityCommands,
+        patterns: &GlobalBulletPatterns,
+    ) {
+        self.phase = phase;
+        self.phase_timer = Timer::from_seconds(phase.duration(), TimerMode::Once);
+        self.attack_timer =
+            Timer::from_seconds(phase.attack_interval(self.enraged), TimerMode::Repeating);
+
+        if phase != BossPhase::Volley {
+            entity.remove::<PatternPlayer>();
+            return;
+        }
+        let pattern = if self.enraged {
+            patterns.boss_volley_enraged.clone()
+        } else {
+            patterns.boss_volley.clone()
+        };
+        entity.insert(PatternPlayer::new(pattern, rand::random(), true));
+    }
+}
+
+fn reset_boss_spawn_timer(mut timer: ResMut<BossSpawnTimer>) {
+    *timer = BossSpawnTimer::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_boss(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut timer: ResMut<BossSpawnTimer>,
+    handle: Res<GlobalTextureAtlas>,
+    patterns: Res<GlobalBulletPatterns>,
+    player_query: Query<&Transform, With<Player>>,
+    boss_query: Query<(), With<Boss>>,
+    mut music_events: EventWriter<MusicChangeEvent>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+) {
+    if !timer.0.tick(time.delta()).just_finished()
+        || player_query.is_empty()
+        || !boss_query.is_empty()
+    {
+        return;
+    }
+
+    let player_pos = player_query.single().translation.truncate();
+    let (x, y) = get_random_position_around(player_pos);
+    let boss = spawn_enemy(&mut commands, &handle, EnemyKind::Boss, vec2(x, y));
+    let mut state = Boss {
+        phase: BossPhase::Volley,
+        phase_timer: Timer::default(),
+        attack_timer: Timer::default(),
+        charge_dir: Vec2::ZERO,
+        enraged: false,
+    };
+    let mut boss = commands.entity(boss);
+    state.enter_phase(BossPhase::Volley, &mut boss, &patterns);
+    boss.insert(state);
+
+    music_events.send(MusicChangeEvent::Boss);
+    shake_events.send(CameraShakeEvent {
+        intensity: 12.0,
+        duration: 1.0,
+    });
+}
+
+fn update_boss_phase(
+    mut commands: Commands,
+    time: Res<Time>,
+    patterns: Res<GlobalBulletPatterns>,
+    mut boss_query: Query<(Entity, &mut Boss, &Health)>,
+) {
+    for (entity, mut boss, health) in boss_query.iter_mut() {
+        if !boss.enraged && health.0 < BOSS_HEALTH * BOSS_ENRAGE_HEALTH_FRACTION {
+            boss.enraged = true;
+        }
+        if boss.phase_timer.tick(time.delta()).finished() {
+            let next = boss.phase.next();
+            boss.enter_phase(next, &mut commands.entity(entity), &patterns);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_boss_attacks(
+    mut commands: Commands,
+    time: Res<Time>,
+    handle: Res<GlobalTextureAtlas>,
+    settings: Res<Settings>,
+    player_query: Query<&Transform, With<Player>>,
+    enemy_query: Query<(), With<Enemy>>,
+    mut boss_query: Query<(&mut Transform, &mut Boss), Without<Player>>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+) {
+    if player_query.is_empty() || boss_query.is_empty() {
+        return;
+    }
+
+    let player_pos = player_query.single().translation.truncate();
+    for (mut transform, mut boss) in boss_query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let to_player = wrapped_delta(pos, player_pos, settings.wraps_world()).normalize_or_zero();
+        let wind_up_before = boss.attack_timer.fraction() < CHARGE_WIND_UP;
+        let attack = boss.attack_timer.tick(time.delta()).just_finished();
+
+        match boss.phase {
+            BossPhase::Volley => {
+                transform.translation += to_player.extend(0.0) * BOSS_SPEED;
+            }
+            BossPhase::Charge => {
+                if boss.attack_timer.fraction() < CHARGE_WIND_UP {
+                    // keep aiming during the wind-up
+                    boss.charge_dir = to_player;
+                    continue;
+                }
+                if wind_up_before && !attack {
+                    shake_events.send(CameraShakeEvent {
+                        intensity: 6.0,
+                        duration: 0.3,
+                    });
+                }
+                transform.translation +=
+                    boss.charge_dir.extend(0.0) * BOSS_CHARGE_SPEED * time.delta_seconds();
+            }
+            BossPhase::Summon => {
+                if !attack {
+                    continue;
+                }
+                let count = if boss.enraged {
+                    BOSS_SUMMON_COUNT * 2
+                } else {
+                    BOSS_SUMMON_COUNT
+                };
+                // capped like wave spawns, the ring just gets sparser
+                let count = count.min(MAX_NUM_ENEMIES.saturating_sub(enemy_query.iter().len()));
+                for i in 0..count {
+                    let angle = i as f32 * 2.0 * PI / count as f32;
+                    let spawn_pos = pos + Vec2::from_angle(angle) * BOSS_COLLISION_RADIUS * 1.5;
+                    spawn_enemy(&mut commands, &handle, EnemyKind::Swarmer, spawn_pos);
+                }
+            }
+        }
+    }
+}
+
+fn handle_boss_death(
+    mut removed: RemovedComponents<Boss>,
+    boss_query: Query<(), With<Boss>>,
+    mut music_events: EventWriter<MusicChangeEvent>,
+    mut shake_events: EventWriter<CameraShakeEvent>,
+) {
+    if removed.read().count() == 0 || !boss_query.is_empty() {
+        return;
+    }
+
+    music_events.send(MusicChangeEvent::Gameplay);
+    shake_events.send(CameraShakeEvent {
+        intensity: 16.0,
+        duration: 0.8,
+    });
+}
@@ -1,18 +1,40 @@
 use bevy::{math::vec3, prelude::*};
-use bevy_pancam::{PanCam, PanCamPlugin};
+use bevy_pancam::{PanCam, PanCamPlugin, PanCamSystemSet};
+use rand::Rng;
 
-use crate::{player::Player, state::GameState};
+use crate::{player::Player, state::GameState, WORLD_HEIGHT};
 
 pub struct FollowCameraPlugin;
 
+/// Shakes the camera by up to `intensity` pixels, fading out over `duration`
+/// seconds. A stronger shake replaces a weaker one that is still running.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CameraShakeEvent {
+    pub intensity: f32,
+    pub duration: f32,
+}
+
+#[derive(Resource, Default)]
+struct CameraShake {
+    intensity: f32,
+    timer: Timer,
+}
+
 impl Plugin for FollowCameraPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(PanCamPlugin)
+            .init_resource::<CameraShake>()
+            .add_event::<CameraShakeEvent>()
             .add_systems(OnEnter(GameState::Loading), setup_camera)
             .add_systems(
                 Update,
-                camera_follow_player.run_if(in_state(GameState::InGame)),
-            );
+                (
+                    camera_follow_player,
+                    shake_camera.after(camera_follow_player),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(Update, toggle_camera_zoom.before(PanCamSystemSet));
     }
 }
 
@@ -35,5 +57,47 @@ fn camera_follow_player(
     let player_transform = player_query.single().translation;
     let (x, y) = (player_transform.x, player_transform.y);
 
-    camera_transform.translation = camera_transform.translation.lerp(vec3(x, y, 0.0), 0.1);
+    let target = vec3(x, y, 0.0);
+    // snap instead of sweeping across the map when the player wraps around
+    if camera_transform.translation.distance(target) > WORLD_HEIGHT {
+        camera_transform.translation = target;
+    } else {
+        camera_transform.translation = camera_transform.translation.lerp(target, 0.1);
+    }
+}
+
+fn shake_camera(
+    time: Res<Time>,
+    mut shake: ResMut<CameraShake>,
+    mut events: EventReader<CameraShakeEvent>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    for event in events.read() {
+        let current = shake.intensity * shake.timer.fraction_remaining();
+        if event.intensity >= current {
+            shake.intensity = event.intensity;
+            shake.timer = Timer::from_seconds(event.duration, TimerMode::Once);
+        }
+    }
+
+    if camera_query.is_empty() || shake.timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    let strength = shake.intensity * shake.timer.fraction_remaining();
+    let offset = vec3(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0), 0.0) * strength;
+    camera_query.single_mut().translation += offset;
+}
+
+// the scroll wheel switches weapons, so only zoom while ctrl is held
+fn toggle_camera_zoom(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_query: Query<&mut PanCam, With<Camera>>,
+) {
+    let zoom_held = keyboard_input.pressed(KeyCode::ControlLeft)
+        || keyboard_input.pressed(KeyCode::ControlRight);
+    for mut pan_cam in camera_query.iter_mut() {
+        pan_cam.enabled = zoom_held;
+    }
 }
@@ -4,90 +4,563 @@ use bevy::prelude::*;
 use bevy::time::common_conditions::on_timer;
 use kd_tree::{KdPoint, KdTree};
 
-use crate::enemy::Enemy;
-use crate::player::{Player, PlayerEnemyCollisionEvent};
+use crate::damage::{DamageEvent, DamageKind, Health, Invulnerable, Knockback};
+use crate::enemy::{Enemy, MAX_KD_TREE_COLLISION_RADIUS};
+use crate::flowfield::FlowField;
+use crate::passive::{Aura, BladeHits, OrbitingBlade};
+use crate::player::Player;
 use crate::state::GameState;
-use crate::weapon::Bullet;
-use crate::{BULLET_DAMAGE, KD_TREE_REFRESH_RATE};
+use crate::weapon::{
+    Beam, Bullet, BulletDirection, Explosive, Faction, HitEnemies, Pierce, Ricochet,
+};
+use crate::world::{wrapped_copies, wrapped_delta, Obstacle};
+use crate::{
+    Settings, BLADE_HIT_COOLDOWN, BLADE_HIT_RADIUS, BULLET_COLLISION_RADIUS, ENEMY_HITSTUN_SECS,
+    KD_TREE_REFRESH_RATE, PLAYER_COLLISION_RADIUS, WORLD_WRAP_QUERY_MARGIN,
+};
 
 pub struct CollisionPlugin;
 
 #[derive(Component)]
-struct Collidable {
-    pos: Vec2,
-    entity: Entity,
+pub struct Collidable {
+    pub pos: Vec2,
+    pub entity: Entity,
+    pub radius: f32,
 }
 
 #[derive(Resource)]
-struct EnemyKdTree(KdTree<Collidable>);
+pub struct EnemyKdTree {
+    pub tree: KdTree<Collidable>,
+    /// Enemies left out of the tree, see `EnemyKind::in_kd_tree`. Refreshed
+    /// every frame and tested one by one.
+    pub large: Vec<Collidable>,
+}
 
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(EnemyKdTree::default()).add_systems(
-            Update,
-            (
-                handle_enemy_bullet_collision,
-                handle_enemy_player_collision,
-                update_enemy_dk_tree
-                    .run_if(on_timer(Duration::from_secs_f32(KD_TREE_REFRESH_RATE))),
+        app.insert_resource(EnemyKdTree::default())
+            .add_systems(
+                Update,
+                (
+                    handle_bullet_obstacle_collision,
+                    handle_enemy_bullet_collision,
+                    handle_player_bullet_collision,
+                    handle_enemy_player_collision,
+                    handle_enemy_beam_collision,
+                    handle_enemy_blade_collision,
+                    handle_enemy_aura_collision,
+                    update_enemy_dk_tree
+                        .run_if(on_timer(Duration::from_secs_f32(KD_TREE_REFRESH_RATE))),
+                    update_large_enemies,
+                )
+                    .run_if(in_state(GameState::InGame)),
             )
-                .run_if(in_state(GameState::InGame)),
-        );
+            // after every system that moves things, so nothing is drawn inside an obstacle
+            .add_systems(
+                PostUpdate,
+                (push_player_out_of_obstacles, push_enemies_out_of_obstacles)
+                    .before(TransformSystem::TransformPropagate)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+fn push_player_out_of_obstacles(
+    mut player_query: Query<&mut Transform, With<Player>>,
+    obstacle_query: Query<(&Transform, &Obstacle), Without<Player>>,
+) {
+    if player_query.is_empty() {
+        return;
+    }
+
+    let mut transform = player_query.single_mut();
+    for (obstacle_transform, obstacle) in obstacle_query.iter() {
+        let center = obstacle_transform.translation.truncate();
+        let pos = transform.translation.truncate();
+        if let Some(push) = obstacle.push_out(center, pos, PLAYER_COLLISION_RADIUS) {
+            transform.translation += push.extend(0.0);
+        }
+    }
+}
+
+fn push_enemies_out_of_obstacles(
+    flow_field: Res<FlowField>,
+    mut enemy_query: Query<(&mut Transform, &Enemy)>,
+    obstacle_query: Query<(&Transform, &Obstacle), Without<Enemy>>,
+) {
+    for (mut transform, enemy) in enemy_query.iter_mut() {
+        let pos = transform.translation.truncate();
+        let radius = enemy.kind.stats().collision_radius;
+        // the flow field already knows where obstacles are, skip the exact test
+        // for enemies out in the open
+        let near_obstacle = [Vec2::ZERO, Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y]
+            .iter()
+            .any(|offset| flow_field.is_blocked(pos + *offset * radius));
+        if !near_obstacle {
+            continue;
+        }
+
+        for (obstacle_transform, obstacle) in obstacle_query.iter() {
+            let center = obstacle_transform.translation.truncate();
+            let pos = transform.translation.truncate();
+            if let Some(push) = obstacle.push_out(center, pos, radius) {
+                transform.translation += push.extend(0.0);
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn handle_bullet_obstacle_collision(
+    mut commands: Commands,
+    mut bullet_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &Bullet,
+            &mut BulletDirection,
+            Option<&mut Ricochet>,
+            Option<&Explosive>,
+            Option<&HitEnemies>,
+        ),
+        Without<Obstacle>,
+    >,
+    obstacle_query: Query<(&Transform, &Obstacle)>,
+    tree: Res<EnemyKdTree>,
+    enemy_query: Query<&Health, With<Enemy>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    if bullet_query.is_empty() || obstacle_query.is_empty() {
+        return;
+    }
+
+    for (entity, mut transform, bullet, mut direction, ricochet, explosive, hit_enemies) in
+        bullet_query.iter_mut()
+    {
+        let pos = transform.translation.truncate();
+        let Some((center, obstacle)) = obstacle_query
+            .iter()
+            .map(|(t, obstacle)| (t.translation.truncate(), obstacle))
+            .find(|(center, obstacle)| obstacle.contains(*center, pos))
+        else {
+            continue;
+        };
+
+        if let Some(mut ricochet) = ricochet.filter(|ricochet| ricochet.0 > 0) {
+            // bounce off the side the bullet went in through
+            let local = pos - center;
+            let depth = obstacle.half_size - local.abs();
+            if depth.x < depth.y {
+                direction.0.x = -direction.0.x;
+                transform.translation.x =
+                    center.x + (obstacle.half_size.x + 1.0) * local.x.signum();
+            } else {
+                direction.0.y = -direction.0.y;
+                transform.translation.y =
+                    center.y + (obstacle.half_size.y + 1.0) * local.y.signum();
+            }
+            ricochet.0 -= 1;
+            continue;
+        }
+
+        if let Some(explosive) = explosive {
+            let already_hit = hit_enemies.map_or(&[][..], |hit| &hit.0);
+            explode(
+                pos,
+                explosive,
+                bullet.source,
+                &tree,
+                &enemy_query,
+                &mut damage_events,
+                already_hit,
+            );
+        }
+        commands.entity(entity).despawn();
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn handle_enemy_player_collision(
-    player_query: Query<&Transform, With<Player>>,
+    player_query: Query<(Entity, &Transform), (With<Player>, Without<Invulnerable>)>,
+    enemy_query: Query<&Enemy>,
     tree: Res<EnemyKdTree>,
-    mut ew: EventWriter<PlayerEnemyCollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
 ) {
     if player_query.is_empty() {
         return;
     }
 
-    let player_pos = player_query.single().translation;
-    let enemies = tree.0.within_radius(&[player_pos.x, player_pos.y], 50.0);
-    for _ in enemies.iter() {
-        ew.send(PlayerEnemyCollisionEvent);
+    // one hit per i-frame window, from the closest enemy touching the player
+    let (player, player_transform) = player_query.single();
+    let player_pos = player_transform.translation.truncate();
+    let closest = tree
+        .within_radius(player_pos, PLAYER_COLLISION_RADIUS)
+        .into_iter()
+        .min_by(|a, b| {
+            a.pos
+                .distance_squared(player_pos)
+                .total_cmp(&b.pos.distance_squared(player_pos))
+        });
+
+    let Some(e) = closest else {
+        return;
+    };
+    if let Ok(enemy) = enemy_query.get(e.entity) {
+        damage_events.send(DamageEvent {
+            target: player,
+            source: e.entity,
+            amount: enemy.kind.stats().contact_damage,
+            kind: DamageKind::Contact,
+        });
     }
 }
 
 fn update_enemy_dk_tree(
+    settings: Res<Settings>,
+    mut tree: ResMut<EnemyKdTree>,
+    enemy_query: Query<(&Transform, Entity, &Enemy)>,
+) {
+    let enemies = enemy_query
+        .iter()
+        .filter(|(_, _, enemy)| enemy.kind.in_kd_tree())
+        .flat_map(|(transform, entity, enemy)| collidables(&settings, transform, entity, enemy))
+        .collect();
+
+    tree.tree = KdTree::build_by_ordered_float(enemies);
+}
+
+fn update_large_enemies(
+    settings: Res<Settings>,
     mut tree: ResMut<EnemyKdTree>,
-    enemy_query: Query<(&Transform, Entity), With<Enemy>>,
+    enemy_query: Query<(&Transform, Entity, &Enemy)>,
 ) {
-    let mut enemies = Vec::new();
-    for (transform, entity) in enemy_query.iter() {
-        enemies.push(Collidable {
+    tree.large = enemy_query
+        .iter()
+        .filter(|(_, _, enemy)| !enemy.kind.in_kd_tree())
+        .flat_map(|(transform, entity, enemy)| collidables(&settings, transform, entity, enemy))
+        .collect();
+}
+
+fn collidables(
+    settings: &Settings,
+    transform: &Transform,
+    entity: Entity,
+    enemy: &Enemy,
+) -> Vec<Collidable> {
+    let pos = transform.translation.truncate();
+    let radius = enemy.kind.stats().collision_radius;
+    // enemies near the seam of a wrapping arena can be hit from both sides
+    let copies = if settings.wraps_world() {
+        wrapped_copies(pos, WORLD_WRAP_QUERY_MARGIN)
+    } else {
+        Vec::new()
+    };
+    std::iter::once(pos)
+        .chain(copies)
+        .map(|pos| Collidable {
             entity,
-            pos: transform.translation.truncate(),
+            pos,
+            radius,
         })
-    }
-
-    tree.0 = KdTree::build_by_ordered_float(enemies);
+        .collect()
 }
 
+#[allow(clippy::type_complexity)]
 fn handle_enemy_bullet_collision(
-    bullet_query: Query<&Transform, With<Bullet>>,
+    mut commands: Commands,
+    mut bullet_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Bullet,
+            &BulletDirection,
+            &mut Pierce,
+            &mut HitEnemies,
+            Option<&Explosive>,
+            &Faction,
+        ),
+        With<Bullet>,
+    >,
     tree: Res<EnemyKdTree>,
-    mut enemy_query: Query<&mut Enemy, With<Enemy>>,
+    enemy_query: Query<&Health, With<Enemy>>,
+    mut damage_events: EventWriter<DamageEvent>,
 ) {
     if bullet_query.is_empty() || enemy_query.is_empty() {
         return;
     }
 
-    for bullet_transform in bullet_query.iter() {
-        let pos = bullet_transform.translation;
-        let enemies = tree.0.within_radius(&[pos.x, pos.y], 50.0);
+    for (
+        bullet_entity,
+        bullet_transform,
+        bullet,
+        direction,
+        mut pierce,
+        mut hit_enemies,
+        explosive,
+        faction,
+    ) in bullet_query.iter_mut()
+    {
+        if *faction != Faction::Player {
+            continue;
+        }
+
+        let pos = bullet_transform.translation.truncate();
+        let mut enemies = tree.within_radius(pos, BULLET_COLLISION_RADIUS);
+        // pierce through the closest enemies first
+        enemies.sort_by(|a, b| {
+            a.pos
+                .distance_squared(pos)
+                .total_cmp(&b.pos.distance_squared(pos))
+        });
 
         for e in enemies {
-            if let Ok(mut enemy) = enemy_query.get_mut(e.entity) {
-                enemy.health -= BULLET_DAMAGE;
+            if hit_enemies.0.contains(&e.entity) {
+                continue;
+            }
+            let hit = DamageEvent {
+                target: e.entity,
+                source: bullet.source,
+                amount: bullet.damage,
+                kind: bullet.kind,
+            };
+            if !damage_enemy(&enemy_query, &mut damage_events, hit) {
+                continue;
+            }
+            hit_enemies.0.push(e.entity);
+            if bullet.knockback > 0.0 {
+                commands.entity(e.entity).try_insert(Knockback {
+                    velocity: direction.0.truncate().normalize_or_zero() * bullet.knockback,
+                    timer: Timer::from_seconds(ENEMY_HITSTUN_SECS, TimerMode::Once),
+                });
+            }
+
+            if let Some(explosive) = explosive {
+                explode(
+                    pos,
+                    explosive,
+                    bullet.source,
+                    &tree,
+                    &enemy_query,
+                    &mut damage_events,
+                    &hit_enemies.0,
+                );
+                commands.entity(bullet_entity).despawn();
+                break;
+            }
+
+            pierce.0 = pierce.0.saturating_sub(1);
+            if pierce.0 == 0 {
+                commands.entity(bullet_entity).despawn();
+                break;
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn handle_player_bullet_collision(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    player_query: Query<(Entity, &Transform), (With<Player>, Without<Invulnerable>)>,
+    bullet_query: Query<(Entity, &Transform, &Bullet, &Faction)>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    if player_query.is_empty() || bullet_query.is_empty() {
+        return;
+    }
+
+    let (player, player_transform) = player_query.single();
+    let player_pos = player_transform.translation.truncate();
+    let hit_distance = PLAYER_COLLISION_RADIUS + BULLET_COLLISION_RADIUS;
+    for (bullet_entity, bullet_transform, bullet, faction) in bullet_query.iter() {
+        if *faction != Faction::Enemy {
+            continue;
+        }
+        let pos = bullet_transform.translation.truncate();
+        let offset = wrapped_delta(pos, player_pos, settings.wraps_world());
+        if offset.length_squared() > hit_distance * hit_distance {
+            continue;
+        }
+
+        damage_events.send(DamageEvent {
+            target: player,
+            source: bullet.source,
+            amount: bullet.damage,
+            kind: bullet.kind,
+        });
+        commands.entity(bullet_entity).despawn();
+        // the i-frames from this hit let the remaining shots pass through
+        break;
+    }
+}
+
+fn explode(
+    pos: Vec2,
+    explosive: &Explosive,
+    source: Entity,
+    tree: &EnemyKdTree,
+    enemy_query: &Query<&Health, With<Enemy>>,
+    damage_events: &mut EventWriter<DamageEvent>,
+    already_hit: &[Entity],
+) {
+    for e in tree.within_radius(pos, explosive.radius) {
+        if already_hit.contains(&e.entity) {
+            continue;
+        }
+        let falloff = 1.0 - e.pos.distance(pos) / explosive.radius;
+        let hit = DamageEvent {
+            target: e.entity,
+            source,
+            amount: explosive.damage * falloff.max(0.0),
+            kind: DamageKind::Explosive,
+        };
+        damage_enemy(enemy_query, damage_events, hit);
+    }
+}
+
+fn handle_enemy_beam_collision(
+    time: Res<Time>,
+    beam_query: Query<(Entity, &Beam)>,
+    tree: Res<EnemyKdTree>,
+    enemy_query: Query<&Health, With<Enemy>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for (source, beam) in beam_query.iter() {
+        for e in tree.within_segment(beam.start, beam.end, beam.width) {
+            let hit = DamageEvent {
+                target: e.entity,
+                source,
+                amount: beam.damage_per_second * time.delta_seconds(),
+                kind: beam.kind,
+            };
+            damage_enemy(&enemy_query, &mut damage_events, hit);
+        }
+    }
+}
+
+fn handle_enemy_blade_collision(
+    time: Res<Time>,
+    mut blade_query: Query<(Entity, &Transform, &OrbitingBlade, &mut BladeHits)>,
+    tree: Res<EnemyKdTree>,
+    enemy_query: Query<&Health, With<Enemy>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    let now = time.elapsed_seconds();
+    for (source, transform, blade, mut hits) in blade_query.iter_mut() {
+        hits.0
+            .retain(|(_, hit_at)| now - hit_at < BLADE_HIT_COOLDOWN);
+
+        let pos = transform.translation.truncate();
+        for e in tree.within_radius(pos, BLADE_HIT_RADIUS) {
+            if hits.0.iter().any(|(entity, _)| *entity == e.entity) {
+                continue;
             }
+            let hit = DamageEvent {
+                target: e.entity,
+                source,
+                amount: blade.damage,
+                kind: DamageKind::Physical,
+            };
+            if damage_enemy(&enemy_query, &mut damage_events, hit) {
+                hits.0.push((e.entity, now));
+            }
+        }
+    }
+}
+
+fn handle_enemy_aura_collision(
+    time: Res<Time>,
+    mut aura_query: Query<(Entity, &Transform, &mut Aura)>,
+    tree: Res<EnemyKdTree>,
+    enemy_query: Query<&Health, With<Enemy>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for (source, transform, mut aura) in aura_query.iter_mut() {
+        if !aura.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let pos = transform.translation.truncate();
+        for e in tree.within_radius(pos, aura.radius) {
+            let hit = DamageEvent {
+                target: e.entity,
+                source,
+                amount: aura.damage,
+                kind: DamageKind::Energy,
+            };
+            damage_enemy(&enemy_query, &mut damage_events, hit);
         }
     }
 }
 
+/// Queues damage against a living enemy. Returns false when the enemy is
+/// already dead or no longer exists.
+fn damage_enemy(
+    enemy_query: &Query<&Health, With<Enemy>>,
+    damage_events: &mut EventWriter<DamageEvent>,
+    hit: DamageEvent,
+) -> bool {
+    if !enemy_query
+        .get(hit.target)
+        .is_ok_and(|health| health.0 > 0.0)
+    {
+        return false;
+    }
+
+    damage_events.send(hit);
+    true
+}
+
+impl EnemyKdTree {
+    pub fn nearest(&self, pos: Vec2) -> Option<&Collidable> {
+        self.tree
+            .nearest(&[pos.x, pos.y])
+            .map(|nearest| nearest.item)
+            .into_iter()
+            .chain(self.large.iter())
+            .min_by(|a, b| {
+                a.pos
+                    .distance_squared(pos)
+                    .total_cmp(&b.pos.distance_squared(pos))
+            })
+    }
+
+    /// Enemies whose collision circle overlaps the circle at `pos`.
+    pub fn within_radius(&self, pos: Vec2, radius: f32) -> Vec<&Collidable> {
+        let padded = radius + MAX_KD_TREE_COLLISION_RADIUS;
+        self.tree
+            .within_radius(&[pos.x, pos.y], padded)
+            .into_iter()
+            .chain(self.large.iter())
+            .filter(|e| e.pos.distance_squared(pos) <= (radius + e.radius).powi(2))
+            .collect()
+    }
+
+    /// Enemies whose collision circle overlaps the segment from `start` to
+    /// `end` swept by `radius`.
+    pub fn within_segment(&self, start: Vec2, end: Vec2, radius: f32) -> Vec<&Collidable> {
+        let padded = radius + MAX_KD_TREE_COLLISION_RADIUS;
+        let min = start.min(end) - Vec2::splat(padded);
+        let max = start.max(end) + Vec2::splat(padded);
+        let segment = end - start;
+        let length_squared = segment.length_squared();
+
+        self.tree
+            .within(&[[min.x, min.y], [max.x, max.y]])
+            .into_iter()
+            .chain(self.large.iter())
+            .filter(|e| {
+                let t = if length_squared > 0.0 {
+                    ((e.pos - start).dot(segment) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                e.pos.distance_squared(start + segment * t) <= (radius + e.radius).powi(2)
+            })
+            .collect()
+    }
+}
+
 impl KdPoint for Collidable {
     type Scalar = f32;
     type Dim = typenum::U2;
@@ -103,6 +576,96 @@ impl KdPoint for Collidable {
 
 impl Default for EnemyKdTree {
     fn default() -> Self {
-        Self(KdTree::build_by_ordered_float(vec![]))
+        Self {
+            tree: KdTree::build_by_ordered_float(vec![]),
+            large: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec2;
+
+    use super::*;
+
+    fn enemy(index: u32, pos: Vec2, radius: f32) -> Collidable {
+        Collidable {
+            entity: Entity::from_raw(index),
+            pos,
+            radius,
+        }
+    }
+
+    fn tree(enemies: Vec<Collidable>) -> EnemyKdTree {
+        EnemyKdTree {
+            tree: KdTree::build_by_ordered_float(enemies),
+            large: Vec::new(),
+        }
+    }
+
+    fn hits(tree: &EnemyKdTree, start: Vec2, end: Vec2, radius: f32) -> Vec<u32> {
+        let mut hits: Vec<_> = tree
+            .within_segment(start, end, radius)
+            .into_iter()
+            .map(|e| e.entity.index())
+            .collect();
+        hits.sort();
+        hits
+    }
+
+    #[test]
+    fn segment_hits_enemies_at_and_around_its_endpoints() {
+        let tree = tree(vec![
+            enemy(0, vec2(0.0, 0.0), 10.0),
+            enemy(1, vec2(100.0, 0.0), 10.0),
+            // past the end, but the circles still touch
+            enemy(2, vec2(114.0, 0.0), 10.0),
+            enemy(3, vec2(-16.0, 0.0), 10.0),
+        ]);
+        assert_eq!(
+            hits(&tree, Vec2::ZERO, vec2(100.0, 0.0), 5.0),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn segment_is_padded_by_both_radii() {
+        let tree = tree(vec![
+            enemy(0, vec2(50.0, 14.0), 10.0),
+            enemy(1, vec2(50.0, -16.0), 10.0),
+            enemy(2, vec2(50.0, 0.0), 1.0),
+        ]);
+        assert_eq!(hits(&tree, Vec2::ZERO, vec2(100.0, 0.0), 5.0), vec![0, 2]);
+        assert_eq!(
+            hits(&tree, Vec2::ZERO, vec2(100.0, 0.0), 7.0),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn zero_length_segment_is_a_circle() {
+        let tree = tree(vec![
+            enemy(0, vec2(10.0, 10.0), 5.0),
+            enemy(1, vec2(0.0, 19.0), 10.0),
+            enemy(2, vec2(0.0, 21.0), 10.0),
+        ]);
+        let pos = Vec2::ZERO;
+        assert_eq!(hits(&tree, pos, pos, 10.0), vec![0, 1]);
+
+        let mut circle: Vec<_> = tree
+            .within_radius(pos, 10.0)
+            .into_iter()
+            .map(|e| e.entity.index())
+            .collect();
+        circle.sort();
+        assert_eq!(hits(&tree, pos, pos, 10.0), circle);
+    }
+
+    #[test]
+    fn segment_includes_large_enemies_outside_the_tree() {
+        let mut tree = tree(vec![enemy(0, vec2(500.0, 500.0), 10.0)]);
+        tree.large.push(enemy(1, vec2(50.0, 100.0), 100.0));
+        assert_eq!(hits(&tree, Vec2::ZERO, vec2(100.0, 0.0), 5.0), vec![1]);
     }
 }
@@ -8,24 +8,129 @@ pub const SPRITE_SCALE_FACTOR: f32 = 3.0;
 pub const TILE_WIDTH: u32 = 16;
 pub const TILE_HEIGHT: u32 = 16;
 pub const SPRITE_SHEET_WIDTH: u32 = 4;
-pub const SPRITE_SHEET_HEIGHT: u32 = 4;
+pub const SPRITE_SHEET_HEIGHT: u32 = 8;
 
 // World
-pub const NUM_WORLD_DECORATIONS: usize = 1000;
 pub const WORLD_WIDTH: f32 = 3000.0;
 pub const WORLD_HEIGHT: f32 = 2500.0;
+pub const GROUND_TILE_SIZE: f32 = 50.0;
+pub const CHUNK_TILES: usize = 16;
+pub const CHUNK_SIZE: f32 = GROUND_TILE_SIZE * CHUNK_TILES as f32;
+pub const BIOME_NOISE_SCALE: f32 = 1500.0;
+pub const BIOME_NOISE_OCTAVES: u32 = 4;
+pub const DECORATION_ATTEMPTS_PER_CHUNK: usize = 40;
+pub const OBSTACLE_ATTEMPTS_PER_CHUNK: usize = 8;
+pub const CHUNK_LOAD_MARGIN: f32 = 400.0;
+pub const CHUNK_UNLOAD_MARGIN: f32 = 1200.0;
+/// Obstacles are placed further out than the ground so enemies off screen
+/// already run into them.
+pub const OBSTACLE_LOAD_MARGIN: f32 = 2500.0;
+pub const OBSTACLE_UNLOAD_MARGIN: f32 = 3300.0;
+pub const OBSTACLE_CLEAR_RADIUS: f32 = 400.0;
+pub const WORLD_SEED_ENV: &str = "BULLETHELL_SEED";
+pub const ARENA_FORMAT_VERSION: u32 = 1;
+pub const ARENA_PATHS: [&str; 1] = ["arenas/crossroads.arena.ron"];
+pub const WORLD_BORDER_THICKNESS: f32 = 200.0;
+pub const WORLD_WRAP_BORDER_THICKNESS: f32 = 8.0;
+/// How close to an edge something has to be to also show up past the opposite
+/// edge in spatial queries. Covers the longest query, the bomb and the beam.
+pub const WORLD_WRAP_QUERY_MARGIN: f32 = 1000.0;
+/// Extra room around the view in which copies past the seam are drawn, so
+/// sprites don't pop in at the edge of the screen.
+pub const WORLD_WRAP_GHOST_MARGIN: f32 = 100.0;
 
 // Player
 pub const PLAYER_SPEED: f32 = 2.0;
 pub const PLAYER_HEALTH: f32 = 100.0;
+pub const PLAYER_IFRAME_SECS: f32 = 0.6;
+pub const PLAYER_FLASH_INTERVAL: f32 = 0.08;
+pub const PLAYER_KNOCKBACK_SPEED: f32 = 600.0;
+pub const PLAYER_KNOCKBACK_SECS: f32 = 0.15;
 
 // Enemy
 pub const MAX_NUM_ENEMIES: usize = 100000;
-pub const ENEMY_DAMAGE: f32 = 1.0;
-pub const SPAWN_RATE_PER_SECOND: usize = 500;
+pub const ENEMY_DAMAGE: f32 = 10.0;
 pub const ENEMEY_HEALTH: f32 = 100.0;
-pub const ENEMY_SPAWN_INTERVAL: f32 = 1.0;
 pub const ENEMEY_SPEED: f32 = 1.0;
+pub const ENEMY_HITSTUN_SECS: f32 = 0.12;
+pub const ENEMY_HIT_FLASH_SECS: f32 = 0.08;
+pub const PLAYER_COLLISION_RADIUS: f32 = 20.0;
+pub const BULLET_COLLISION_RADIUS: f32 = 20.0;
+pub const ENEMY_SEPARATION_WEIGHT: f32 = 1.5;
+pub const ENEMY_AVOIDANCE_WEIGHT: f32 = 0.6;
+pub const ENEMY_MAX_NEIGHBOURS: usize = 8;
+pub const ENEMY_MAX_STEER: f32 = 1.5;
+pub const FLOW_FIELD_CELL_SIZE: f32 = 50.0;
+pub const FLOW_FIELD_BUILD_BUDGET: usize = 4000;
+pub const FLOW_FIELD_OBSTACLE_MARGIN: f32 = 20.0;
+
+// Waves
+pub const WAVE_SCHEDULE_PATH: &str = "waves/default.waves.ron";
+pub const FORMATION_RING_RADIUS: f32 = 1200.0;
+pub const FORMATION_CLUSTER_RADIUS: f32 = 150.0;
+pub const FORMATION_LINE_SPACING: f32 = 60.0;
+
+// Adaptive director
+pub const ADAPTIVE_EVAL_INTERVAL: f32 = 3.0;
+pub const ADAPTIVE_TARGET_LOW: f32 = 0.25;
+pub const ADAPTIVE_TARGET_HIGH: f32 = 0.65;
+pub const ADAPTIVE_HIT_INTENSITY: f32 = 2.0;
+pub const ADAPTIVE_HIT_HOLD_SECS: f32 = 3.0;
+pub const ADAPTIVE_INTENSITY_DECAY: f32 = 0.08;
+pub const ADAPTIVE_DOMINATING_KILL_RATE: f32 = 8.0;
+pub const ADAPTIVE_DOMINATING_SECS: f32 = 15.0;
+pub const ADAPTIVE_STEP: f32 = 1.15;
+pub const ADAPTIVE_UPGRADE_STEP: f32 = 0.05;
+pub const ADAPTIVE_MAX_UPGRADE_CHANCE: f32 = 0.5;
+pub const ADAPTIVE_MIN_SPAWN_SCALE: f32 = 0.4;
+pub const ADAPTIVE_MAX_SPAWN_SCALE: f32 = 3.0;
+
+// Boss
+pub const BOSS_SPAWN_INTERVAL: f32 = 120.0;
+pub const BOSS_HEALTH: f32 = 20000.0;
+pub const BOSS_CONTACT_DAMAGE: f32 = 25.0;
+pub const BOSS_SIZE: f32 = 5.0;
+pub const BOSS_COLLISION_RADIUS: f32 = 100.0;
+pub const BOSS_SPEED: f32 = 0.6;
+pub const BOSS_ENRAGE_HEALTH_FRACTION: f32 = 0.5;
+pub const BOSS_VOLLEY_SECS: f32 = 6.0;
+pub const BOSS_VOLLEY_PATTERN_PATH: &str = "patterns/boss_volley.pattern.ron";
+pub const BOSS_VOLLEY_ENRAGED_PATTERN_PATH: &str = "patterns/boss_volley_enraged.pattern.ron";
+/// Shortest time a looping bullet pattern takes to start over.
+pub const PATTERN_MIN_LOOP_SECS: f32 = 0.25;
+pub const BOSS_CHARGE_SECS: f32 = 4.0;
+pub const BOSS_CHARGE_INTERVAL: f32 = 1.3;
+pub const BOSS_CHARGE_SPEED: f32 = 1400.0;
+pub const BOSS_SUMMON_SECS: f32 = 2.0;
+pub const BOSS_SUMMON_INTERVAL: f32 = 0.6;
+pub const BOSS_SUMMON_COUNT: usize = 6;
+
+// Experience
+pub const XP_GEM_SIZE: f32 = 10.0;
+pub const XP_MAGNET_RADIUS: f32 = 150.0;
+pub const XP_GEM_SPEED: f32 = 600.0;
+/// Gems left lying around for this long vanish.
+pub const XP_GEM_LIFETIME_SECS: f32 = 60.0;
+/// A gem dropped this close to another one is added to it instead.
+pub const XP_GEM_MERGE_RADIUS: f32 = 40.0;
+pub const XP_FIRST_LEVEL: f32 = 5.0;
+pub const XP_LEVEL_GROWTH: f32 = 1.3;
+pub const UPGRADE_CHOICES: usize = 3;
+pub const UPGRADE_DAMAGE_STEP: f32 = 0.15;
+pub const UPGRADE_FIRE_RATE_STEP: f32 = 0.12;
+pub const UPGRADE_MOVE_SPEED_STEP: f32 = 0.1;
+
+// Pickups
+pub const PICKUP_SIZE: f32 = 18.0;
+pub const PICKUP_COLLECT_RADIUS: f32 = 30.0;
+pub const PICKUP_LIFETIME_SECS: f32 = 30.0;
+pub const PICKUP_HEAL_AMOUNT: f32 = 30.0;
+pub const PICKUP_BOMB_RADIUS: f32 = 800.0;
+pub const PICKUP_BOMB_DAMAGE: f32 = 1000.0;
+pub const POWERUP_MAGNET_SECS: f32 = 8.0;
+pub const POWERUP_RAPID_FIRE_SECS: f32 = 8.0;
+pub const POWERUP_RAPID_FIRE_SCALE: f32 = 2.0;
+pub const POWERUP_INVULNERABILITY_SECS: f32 = 6.0;
 
 // Kd-tree
 pub const KD_TREE_REFRESH_RATE: f32 = 0.2;
@@ -34,8 +139,23 @@ pub const KD_TREE_REFRESH_RATE: f32 = 0.2;
 pub const BG_COLOR: (f32, f32, f32) = (0.773, 0.8, 0.723);
 
 // Weapon
-pub const BULLET_SPAWN_INTERVAL: f32 = 0.1;
-pub const BULLET_SPEED: f32 = 15.0;
-pub const BULLET_DAMAGE: f32 = 100.0;
-pub const BULLET_TIME_SECS: f32 = 0.5;
-pub const NUM_BULLETS_PER_SHOT: usize = 5;
+pub const STARTING_WEAPON_PATHS: [&str; 1] = ["weapons/rifle.weapon.ron"];
+/// Offered as level up upgrades.
+pub const UNLOCKABLE_WEAPON_PATHS: [&str; 4] = [
+    "weapons/shotgun.weapon.ron",
+    "weapons/grenade_launcher.weapon.ron",
+    "weapons/missile_launcher.weapon.ron",
+    "weapons/laser.weapon.ron",
+];
+pub const MAX_WEAPON_SLOTS: usize = 9;
+
+// Passive weapons
+pub const BLADE_DAMAGE: f32 = 60.0;
+pub const BLADE_MAX_COUNT: u32 = 8;
+pub const BLADE_ORBIT_RADIUS: f32 = 120.0;
+pub const BLADE_ANGULAR_SPEED: f32 = 3.0;
+pub const BLADE_HIT_RADIUS: f32 = 15.0;
+pub const BLADE_HIT_COOLDOWN: f32 = 0.5;
+pub const AURA_RADIUS: f32 = 100.0;
+pub const AURA_DAMAGE: f32 = 25.0;
+pub const AURA_TICK_INTERVAL: f32 = 0.5;
@@ -0,0 +1,298 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::state::GameState;
+
+pub struct DamagePlugin;
+
+#[derive(Component)]
+pub struct Health(pub f32);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum DamageKind {
+    #[default]
+    Physical,
+    Explosive,
+    Energy,
+    Contact,
+}
+
+/// A request to damage `target`. Armor, resistances and crits are applied when
+/// the event is resolved.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub source: Entity,
+    pub amount: f32,
+    pub kind: DamageKind,
+}
+
+/// Sent for every resolved hit with the damage actually applied. On-hit
+/// effects, stats, sounds and floating numbers should read these.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageDealtEvent {
+    pub target: Entity,
+    pub source: Entity,
+    pub amount: f32,
+    pub kind: DamageKind,
+    pub crit: bool,
+    pub killed: bool,
+}
+
+/// Reduces all incoming damage by `armor / (100 + armor)`.
+#[derive(Component)]
+pub struct Armor(pub f32);
+
+/// Fraction of each damage kind that is ignored, from 0.0 to 1.0.
+#[derive(Component, Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct Resistances {
+    pub physical: f32,
+    pub explosive: f32,
+    pub energy: f32,
+    pub contact: f32,
+}
+
+/// Grants `Invulnerable` for this many seconds after taking damage.
+#[derive(Component)]
+pub struct IFrames(pub f32);
+
+#[derive(Component, Deref, DerefMut)]
+pub struct Invulnerable(pub Timer);
+
+/// Pushes an entity along `velocity` (per second) until the timer finishes.
+#[derive(Component)]
+pub struct Knockback {
+    pub velocity: Vec2,
+    pub timer: Timer,
+}
+
+/// Crit stats of a damage source such as a weapon.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Critical {
+    pub chance: f32,
+    pub multiplier: f32,
+}
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<DamageDealtEvent>()
+            .add_systems(
+                Update,
+                (tick_invulnerability, apply_knockback).run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                PostUpdate,
+                resolve_damage.run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+impl Resistances {
+    pub fn get(&self, kind: DamageKind) -> f32 {
+        match kind {
+            DamageKind::Physical => self.physical,
+            DamageKind::Explosive => self.explosive,
+            DamageKind::Energy => self.energy,
+            DamageKind::Contact => self.contact,
+        }
+    }
+}
+
+impl Critical {
+    /// Whether a hit crits for a uniform `roll` in `0.0..1.0`.
+    pub fn rolls(&self, roll: f32) -> bool {
+        roll < self.chance
+    }
+}
+
+impl Invulnerable {
+    /// Lasts until removed, for effects that keep track of their own duration.
+    /// The timer still runs so the player keeps flashing.
+    pub fn until_removed() -> Self {
+        Self(Timer::new(Duration::MAX, TimerMode::Once))
+    }
+}
+
+/// Damage left after a crit, resistances and armor are applied, in that order.
+pub fn mitigated_damage(
+    amount: f32,
+    kind: DamageKind,
+    critical: Option<&Critical>,
+    resistances: Option<&Resistances>,
+    armor: Option<&Armor>,
+) -> f32 {
+    let mut amount = amount;
+    if let Some(critical) = critical {
+        amount *= critical.multiplier;
+    }
+    if let Some(resistances) = resistances {
+        amount *= 1.0 - resistances.get(kind).clamp(0.0, 1.0);
+    }
+    if let Some(armor) = armor {
+        amount *= 100.0 / (100.0 + armor.0.max(0.0));
+    }
+    amount
+}
+
+fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Invulnerable)>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        if invulnerable.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+fn apply_knockback(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut Knockback)>,
+) {
+    for (entity, mut transform, mut knockback) in query.iter_mut() {
+        transform.translation += knockback.velocity.extend(0.0) * time.delta_seconds();
+        if knockback.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn resolve_damage(
+    mut commands: Commands,
+    mut events: EventReader<DamageEvent>,
+    mut dealt_events: EventWriter<DamageDealtEvent>,
+    mut target_query: Query<(
+        &mut Health,
+        Option<&Armor>,
+        Option<&Resistances>,
+        Option<&IFrames>,
+        Has<Invulnerable>,
+    )>,
+    crit_query: Query<&Critical>,
+) {
+    let mut rng = rand::thread_rng();
+    // entities whose i-frames started this frame, before the insert is applied
+    let mut invulnerable_now = Vec::new();
+    for event in events.read() {
+        let Ok((mut health, armor, resistances, iframes, invulnerable)) =
+            target_query.get_mut(event.target)
+        else {
+            continue;
+        };
+        if health.0 <= 0.0 || invulnerable || invulnerable_now.contains(&event.target) {
+            continue;
+        }
+
+        let critical = crit_query
+            .get(event.source)
+            .ok()
+            .filter(|critical| critical.rolls(rng.gen()));
+        let amount = mitigated_damage(event.amount, event.kind, critical, resistances, armor);
+
+        health.0 -= amount;
+        if let Some(iframes) = iframes {
+            invulnerable_now.push(event.target);
+            commands
+                .entity(event.target)
+                .insert(Invulnerable(Timer::from_seconds(
+                    iframes.0,
+                    TimerMode::Once,
+                )));
+        }
+        dealt_events.send(DamageDealtEvent {
+            target: event.target,
+            source: event.source,
+            amount,
+            kind: event.kind,
+            crit: critical.is_some(),
+            killed: health.0 <= 0.0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn unmitigated_damage_is_unchanged() {
+        let amount = mitigated_damage(40.0, DamageKind::Physical, None, None, None);
+        assert_close(amount, 40.0);
+    }
+
+    #[test]
+    fn armor_reduces_by_its_share_of_a_hundred() {
+        let hit =
+            |armor| mitigated_damage(100.0, DamageKind::Energy, None, None, Some(&Armor(armor)));
+        assert_close(hit(0.0), 100.0);
+        assert_close(hit(100.0), 50.0);
+        assert_close(hit(300.0), 25.0);
+        // negative armor doesn't amplify damage
+        assert_close(hit(-50.0), 100.0);
+    }
+
+    #[test]
+    fn resistances_only_apply_to_their_kind_and_are_clamped() {
+        let resistances = Resistances {
+            physical: 0.25,
+            explosive: 1.5,
+            energy: -1.0,
+            contact: 0.0,
+        };
+        let hit = |kind| mitigated_damage(100.0, kind, None, Some(&resistances), None);
+        assert_close(hit(DamageKind::Physical), 75.0);
+        assert_close(hit(DamageKind::Explosive), 0.0);
+        assert_close(hit(DamageKind::Energy), 100.0);
+        assert_close(hit(DamageKind::Contact), 100.0);
+    }
+
+    #[test]
+    fn crits_multiply_before_mitigation() {
+        let critical = Critical {
+            chance: 0.25,
+            multiplier: 2.0,
+        };
+        let resistances = Resistances {
+            physical: 0.5,
+            ..default()
+        };
+        let amount = mitigated_damage(
+            30.0,
+            DamageKind::Physical,
+            Some(&critical),
+            Some(&resistances),
+            Some(&Armor(100.0)),
+        );
+        assert_close(amount, 15.0);
+    }
+
+    #[test]
+    fn crits_roll_against_their_chance() {
+        let critical = Critical {
+            chance: 0.25,
+            multiplier: 2.0,
+        };
+        assert!(critical.rolls(0.0));
+        assert!(critical.rolls(0.24));
+        assert!(!critical.rolls(0.25));
+        assert!(!critical.rolls(0.99));
+
+        let never = Critical {
+            chance: 0.0,
+            multiplier: 2.0,
+        };
+        assert!(!never.rolls(0.0));
+    }
+}
@@ -0,0 +1,331 @@
+use std::f32::consts::PI;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::math::vec2;
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::adaptive::AdaptiveDirector;
+use crate::arena::{Arena, SpawnZone};
+use crate::damage::Health;
+use crate::enemy::{get_random_position_around, spawn_enemy, Enemy, EnemyKind};
+use crate::player::Player;
+use crate::state::GameState;
+use crate::*;
+
+pub struct DirectorPlugin;
+
+/// The scripted waves of a run, loaded from a `.waves.ron` file. Once the last
+/// wave ends the schedule starts over with more and tougher enemies.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct WaveSchedule {
+    pub waves: Vec<Wave>,
+    /// Applied once per completed loop of the schedule.
+    pub loop_count_multiplier: f32,
+    pub loop_health_multiplier: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Wave {
+    pub duration: f32,
+    /// Quiet time after the wave before the next one starts.
+    #[serde(default)]
+    pub intermission: f32,
+    pub spawns: Vec<SpawnGroup>,
+}
+
+/// `count` enemies of one kind arriving `at` seconds into the wave, then again
+/// every `every` seconds for `repeats` more times.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnGroup {
+    pub at: f32,
+    pub kind: EnemyKind,
+    pub count: u32,
+    #[serde(default)]
+    pub formation: Formation,
+    #[serde(default)]
+    pub repeats: u32,
+    #[serde(default)]
+    pub every: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum Formation {
+    /// Spread randomly around the player.
+    #[default]
+    Scattered,
+    /// Evenly spaced on a circle closing in on the player.
+    Ring,
+    /// Bunched up at one random point.
+    Cluster,
+    /// A wall on one side of the player.
+    Line,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WavePhase {
+    #[default]
+    Waiting,
+    Wave,
+    Intermission,
+}
+
+#[derive(Resource, Default)]
+pub struct WaveDirector {
+    /// Waves started this run, including every loop of the schedule.
+    pub wave_number: u32,
+    pub phase: WavePhase,
+    /// Seconds since the current wave or intermission started.
+    pub elapsed: f32,
+    /// The schedule being played, the selected arena's or the default one.
+    pub schedule: Handle<WaveSchedule>,
+    spawn_zones: Vec<SpawnZone>,
+    loop_count: u32,
+    pending: Vec<PendingSpawn>,
+}
+
+struct PendingSpawn {
+    at: f32,
+    kind: EnemyKind,
+    count: u32,
+    formation: Formation,
+}
+
+#[derive(Default)]
+pub struct WaveScheduleLoader;
+
+#[derive(Debug, Error)]
+pub enum WaveScheduleLoaderError {
+    #[error("could not read wave schedule: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse wave schedule: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WaveSchedule>()
+            .init_asset_loader::<WaveScheduleLoader>()
+            .init_resource::<WaveDirector>()
+            .add_systems(OnEnter(GameState::GameInit), reset_wave_director)
+            .add_systems(
+                Update,
+                run_wave_director.run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+impl AssetLoader for WaveScheduleLoader {
+    type Asset = WaveSchedule;
+    type Settings = ();
+    type Error = WaveScheduleLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<WaveSchedule, WaveScheduleLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+impl WaveDirector {
+    /// Seconds until the current wave or intermission ends.
+    pub fn remaining(&self, schedule: &WaveSchedule) -> f32 {
+        let Some(wave) = self.current_wave(schedule) else {
+            return 0.0;
+        };
+        let length = match self.phase {
+            WavePhase::Waiting => 0.0,
+            WavePhase::Wave => wave.duration,
+            WavePhase::Intermission => wave.intermission,
+        };
+        (length - self.elapsed).max(0.0)
+    }
+
+    fn current_wave<'a>(&self, schedule: &'a WaveSchedule) -> Option<&'a Wave> {
+        if schedule.waves.is_empty() || self.wave_number == 0 {
+            return None;
+        }
+        schedule
+            .waves
+            .get((self.wave_number - 1) as usize % schedule.waves.len())
+    }
+
+    fn start_next_wave(&mut self, schedule: &WaveSchedule) {
+        self.wave_number += 1;
+        self.loop_count = (self.wave_number - 1) / schedule.waves.len() as u32;
+        self.phase = WavePhase::Wave;
+        self.elapsed = 0.0;
+
+        let count_scale = schedule.loop_count_multiplier.powi(self.loop_count as i32);
+        let Some(wave) = self.current_wave(schedule) else {
+            return;
+        };
+        let mut pending = Vec::new();
+        for group in wave.spawns.iter() {
+            let count = (group.count as f32 * count_scale).round() as u32;
+            for i in 0..=group.repeats {
+                pending.push(PendingSpawn {
+                    at: group.at + group.every * i as f32,
+                    kind: group.kind,
+                    count,
+                    formation: group.formation,
+                });
+            }
+        }
+        // latest first so due spawns pop off the end
+        pending.sort_by(|a, b| b.at.total_cmp(&a.at));
+        self.pending = pending;
+    }
+}
+
+fn reset_wave_director(
+    mut director: ResMut<WaveDirector>,
+    settings: Res<Settings>,
+    schedule: Res<GlobalWaveSchedule>,
+    global_arenas: Res<GlobalArenas>,
+    arenas: Res<Assets<Arena>>,
+) {
+    let arena = global_arenas.selected(&settings, &arenas);
+    *director = WaveDirector {
+        schedule: arena
+            .and_then(|arena| arena.wave_schedule.clone())
+            .unwrap_or_else(|| schedule.schedule.clone()),
+        spawn_zones: arena.map_or(Vec::new(), |arena| arena.spawn_zones.clone()),
+        ..default()
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_wave_director(
+    mut commands: Commands,
+    time: Res<Time>,
+    handle: Res<GlobalTextureAtlas>,
+    schedules: Res<Assets<WaveSchedule>>,
+    mut director: ResMut<WaveDirector>,
+    adaptive: Res<AdaptiveDirector>,
+    player_query: Query<&Transform, With<Player>>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    if player_query.is_empty() {
+        return;
+    }
+    let Some(schedule) = schedules.get(&director.schedule) else {
+        return;
+    };
+    if schedule.waves.is_empty() {
+        return;
+    }
+
+    director.elapsed += time.delta_seconds();
+    match director.phase {
+        WavePhase::Waiting => director.start_next_wave(schedule),
+        WavePhase::Wave if director.remaining(schedule) <= 0.0 => {
+            director.phase = WavePhase::Intermission;
+            director.elapsed = 0.0;
+            director.pending.clear();
+        }
+        WavePhase::Intermission if director.remaining(schedule) <= 0.0 => {
+            director.start_next_wave(schedule);
+        }
+        _ => {}
+    }
+
+    let health_scale = schedule
+        .loop_health_multiplier
+        .powi(director.loop_count as i32);
+    let player_pos = player_query.single().translation.truncate();
+    let mut num_enemies = enemy_query.iter().len();
+    let mut rng = rand::thread_rng();
+    while director
+        .pending
+        .last()
+        .is_some_and(|spawn| spawn.at <= director.elapsed)
+    {
+        let spawn = director.pending.pop().unwrap();
+        let count = (spawn.count as f32 * adaptive.spawn_scale).round() as usize;
+        let count = count.min(MAX_NUM_ENEMIES.saturating_sub(num_enemies));
+        let positions = formation_positions(
+            spawn.formation,
+            count,
+            player_pos,
+            &director.spawn_zones,
+            &mut rng,
+        );
+        for pos in positions {
+            let kind = adaptive.pick_kind(spawn.kind, &mut rng);
+            let enemy = spawn_enemy(&mut commands, &handle, kind, pos);
+            if health_scale != 1.0 {
+                commands
+                    .entity(enemy)
+                    .insert(Health(kind.stats().health * health_scale));
+            }
+        }
+        num_enemies += count;
+    }
+}
+
+/// Arenas with spawn zones bring scattered and clustered enemies in through
+/// them, rings and lines still close in around the player.
+fn random_spawn_position(player_pos: Vec2, spawn_zones: &[SpawnZone], rng: &mut impl Rng) -> Vec2 {
+    if spawn_zones.is_empty() {
+        let (x, y) = get_random_position_around(player_pos);
+        return vec2(x, y);
+    }
+    let zone = spawn_zones[rng.gen_range(0..spawn_zones.len())];
+    let angle = rng.gen_range(0.0..2.0 * PI);
+    zone.center() + Vec2::from_angle(angle) * rng.gen_range(0.0..=zone.radius)
+}
+
+fn formation_positions(
+    formation: Formation,
+    count: usize,
+    player_pos: Vec2,
+    spawn_zones: &[SpawnZone],
+    rng: &mut impl Rng,
+) -> Vec<Vec2> {
+    match formation {
+        Formation::Scattered => (0..count)
+            .map(|_| random_spawn_position(player_pos, spawn_zones, rng))
+            .collect(),
+        Formation::Ring => {
+            let offset = rng.gen_range(0.0..2.0 * PI);
+            (0..count)
+                .map(|i| {
+                    let angle = offset + i as f32 * 2.0 * PI / count as f32;
+                    player_pos + Vec2::from_angle(angle) * FORMATION_RING_RADIUS
+                })
+                .collect()
+        }
+        Formation::Cluster => {
+            let center = random_spawn_position(player_pos, spawn_zones, rng);
+            (0..count)
+                .map(|_| {
+                    let angle = rng.gen_range(0.0..2.0 * PI);
+                    let dist = rng.gen_range(0.0..FORMATION_CLUSTER_RADIUS);
+                    center + Vec2::from_angle(angle) * dist
+                })
+                .collect()
+        }
+        Formation::Line => {
+            let dir = Vec2::from_angle(rng.gen_range(0.0..2.0 * PI));
+            let center = player_pos + dir * FORMATION_RING_RADIUS;
+            let across = dir.perp();
+            let half_width = (count.saturating_sub(1)) as f32 * FORMATION_LINE_SPACING / 2.0;
+            (0..count)
+                .map(|i| center + across * (i as f32 * FORMATION_LINE_SPACING - half_width))
+                .collect()
+        }
+    }
+}
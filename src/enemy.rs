@@ -1,30 +1,91 @@
 use std::f32::consts::PI;
-use std::time::Duration;
 
 use animation::AnimationTimer;
 use bevy::math::vec3;
-use bevy::{prelude::*, time::common_conditions::on_timer};
+use bevy::prelude::*;
 use rand::Rng;
-use world::GameEntity;
+use serde::Deserialize;
+use world::{wrapped_delta, GameEntity};
 
+use crate::collision::EnemyKdTree;
+use crate::damage::{Armor, DamageKind, Health, Knockback, Resistances};
+use crate::flowfield::FlowField;
 use crate::player::Player;
 use crate::state::GameState;
+use crate::weapon::{Bullet, BulletDirection, Faction};
 use crate::*;
 
+/// Sent when an enemy dies, just before it's despawned.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct EnemyDiedEvent {
+    pub kind: EnemyKind,
+    pub pos: Vec2,
+}
+
 #[derive(Component)]
 pub struct Enemy {
+    pub kind: EnemyKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum EnemyKind {
+    #[default]
+    Grunt,
+    Runner,
+    Tank,
+    Swarmer,
+    Spitter,
+    /// Spawned on its own timer, see `boss.rs`.
+    Boss,
+}
+
+/// Stats of an enemy that keeps its distance and shoots at the player.
+#[derive(Clone, Copy)]
+pub struct RangedAttack {
+    pub preferred_distance: f32,
+    pub fire_interval: f32,
+    pub projectile: EnemyProjectile,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct EnemyProjectile {
+    pub damage: f32,
+    pub speed: f32,
+    pub lifetime: f32,
+    pub sprite_index: usize,
+}
+
+#[derive(Component, Deref, DerefMut)]
+pub struct EnemyAttackTimer(pub Timer);
+
+pub struct EnemyStats {
+    pub speed: f32,
     pub health: f32,
+    pub contact_damage: f32,
+    pub armor: f32,
+    pub resistances: Option<Resistances>,
+    /// Sprite scale relative to `SPRITE_SCALE_FACTOR`.
+    pub size: f32,
+    pub collision_radius: f32,
+    pub color: Color,
+    pub first_frame: usize,
+    pub frame_count: usize,
+    pub frame_time: f32,
+    pub xp: u32,
+    /// Chance of dropping a pickup on death, before `AdaptiveDirector::drop_scale`.
+    pub drop_chance: f32,
+    pub ranged: Option<RangedAttack>,
 }
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.add_event::<EnemyDiedEvent>().add_systems(
             Update,
             (
-                spawn_enemies.run_if(on_timer(Duration::from_secs_f32(ENEMY_SPAWN_INTERVAL))),
                 update_enemy_transform,
+                fire_enemy_projectiles,
                 despawn_dead_enemies,
             )
                 .run_if(in_state(GameState::InGame)),
@@ -32,68 +93,392 @@ impl Plugin for EnemyPlugin {
     }
 }
 
-fn despawn_dead_enemies(mut commands: Commands, enemy_query: Query<(&Enemy, Entity), With<Enemy>>) {
+impl EnemyKind {
+    pub const ALL: [EnemyKind; 6] = [
+        EnemyKind::Grunt,
+        EnemyKind::Runner,
+        EnemyKind::Tank,
+        EnemyKind::Swarmer,
+        EnemyKind::Spitter,
+        EnemyKind::Boss,
+    ];
+
+    pub fn stats(self) -> &'static EnemyStats {
+        &ENEMY_STATS[self as usize]
+    }
+
+    /// Bosses are too big to pad every kd-tree query for, they're kept out of
+    /// the tree and tested on their own.
+    pub fn in_kd_tree(self) -> bool {
+        self != EnemyKind::Boss
+    }
+}
+
+/// Largest collision radius of the kinds in the kd-tree, pads its queries.
+pub const MAX_KD_TREE_COLLISION_RADIUS: f32 = 50.0;
+
+/// Stats of every kind, in the order of `EnemyKind`.
+static ENEMY_STATS: [EnemyStats; 6] = [
+    // Grunt
+    EnemyStats {
+        speed: ENEMEY_SPEED,
+        health: ENEMEY_HEALTH,
+        contact_damage: ENEMY_DAMAGE,
+        armor: 0.0,
+        resistances: None,
+        size: 1.0,
+        collision_radius: 30.0,
+        color: Color::WHITE,
+        first_frame: 8,
+        frame_count: 4,
+        frame_time: 0.08,
+        xp: 1,
+        drop_chance: 0.02,
+        ranged: None,
+    },
+    // Runner
+    EnemyStats {
+        speed: ENEMEY_SPEED * 2.2,
+        health: ENEMEY_HEALTH * 0.4,
+        contact_damage: ENEMY_DAMAGE * 0.5,
+        armor: 0.0,
+        resistances: None,
+        size: 0.8,
+        collision_radius: 24.0,
+        color: Color::srgb(1.0, 0.85, 0.4),
+        first_frame: 16,
+        frame_count: 4,
+        frame_time: 0.05,
+        xp: 1,
+        drop_chance: 0.015,
+        ranged: None,
+    },
+    // Tank
+    EnemyStats {
+        speed: ENEMEY_SPEED * 0.5,
+        health: ENEMEY_HEALTH * 6.0,
+        contact_damage: ENEMY_DAMAGE * 2.0,
+        armor: 50.0,
+        // shrugs off bullets, blow it up or burn it instead
+        resistances: Some(Resistances {
+            physical: 0.4,
+            explosive: 0.0,
+            energy: 0.0,
+            contact: 0.0,
+        }),
+        size: 1.8,
+        collision_radius: 50.0,
+        color: Color::srgb(0.6, 0.7, 1.0),
+        first_frame: 20,
+        frame_count: 4,
+        frame_time: 0.16,
+        xp: 5,
+        drop_chance: 0.15,
+        ranged: None,
+    },
+    // Swarmer
+    EnemyStats {
+        speed: ENEMEY_SPEED * 1.4,
+        health: ENEMEY_HEALTH * 0.2,
+        contact_damage: ENEMY_DAMAGE * 0.3,
+        armor: 0.0,
+        resistances: None,
+        size: 0.6,
+        collision_radius: 18.0,
+        color: Color::srgb(0.7, 1.0, 0.6),
+        first_frame: 24,
+        frame_count: 4,
+        frame_time: 0.06,
+        xp: 1,
+        drop_chance: 0.005,
+        ranged: None,
+    },
+    // Spitter
+    EnemyStats {
+        speed: ENEMEY_SPEED * 1.2,
+        health: ENEMEY_HEALTH * 0.8,
+        contact_damage: ENEMY_DAMAGE * 0.5,
+        armor: 0.0,
+        resistances: None,
+        size: 1.0,
+        collision_radius: 30.0,
+        color: Color::srgb(1.0, 0.5, 0.9),
+        first_frame: 28,
+        frame_count: 4,
+        frame_time: 0.1,
+        xp: 3,
+        drop_chance: 0.04,
+        ranged: Some(RangedAttack {
+            preferred_distance: 450.0,
+            fire_interval: 2.0,
+            projectile: EnemyProjectile {
+                damage: ENEMY_DAMAGE,
+                speed: 6.0,
+                lifetime: 4.0,
+                sprite_index: 15,
+            },
+        }),
+    },
+    // Boss, it steers itself so the regular chase is disabled
+    EnemyStats {
+        speed: 0.0,
+        health: BOSS_HEALTH,
+        contact_damage: BOSS_CONTACT_DAMAGE,
+        armor: 30.0,
+        resistances: Some(Resistances {
+            physical: 0.0,
+            explosive: 0.5,
+            energy: 0.25,
+            contact: 0.0,
+        }),
+        size: BOSS_SIZE,
+        collision_radius: BOSS_COLLISION_RADIUS,
+        color: Color::srgb(1.0, 0.4, 0.4),
+        first_frame: 20,
+        frame_count: 4,
+        frame_time: 0.2,
+        xp: 100,
+        drop_chance: 1.0,
+        ranged: None,
+    },
+];
+
+fn despawn_dead_enemies(
+    mut commands: Commands,
+    mut events: EventWriter<EnemyDiedEvent>,
+    enemy_query: Query<(&Health, &Transform, &Enemy, Entity)>,
+) {
     if enemy_query.is_empty() {
         return;
     }
 
-    for (enemy, entity) in enemy_query.iter() {
-        if enemy.health <= 0.0 {
+    for (health, transform, enemy, entity) in enemy_query.iter() {
+        if health.0 <= 0.0 {
+            events.send(EnemyDiedEvent {
+                kind: enemy.kind,
+                pos: transform.translation.truncate(),
+            });
             commands.entity(entity).despawn();
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn update_enemy_transform(
+    settings: Res<Settings>,
+    tree: Res<EnemyKdTree>,
+    flow_field: Res<FlowField>,
     player_query: Query<&Transform, With<Player>>,
-    mut enemy_query: Query<&mut Transform, (With<Enemy>, Without<Player>)>,
+    mut enemy_query: Query<(Entity, &mut Transform, &Enemy), (Without<Player>, Without<Knockback>)>,
 ) {
     if player_query.is_empty() || enemy_query.is_empty() {
         return;
     }
 
-    let player_pos = player_query.single().translation;
-    for mut transform in enemy_query.iter_mut() {
-        let dir = (player_pos - transform.translation).normalize();
-        transform.translation += dir * ENEMEY_SPEED;
+    // knocked back enemies are stunned and skip steering
+    let player_pos = player_query.single().translation.truncate();
+    for (entity, mut transform, enemy) in enemy_query.iter_mut() {
+        let stats = enemy.kind.stats();
+        let pos = transform.translation.truncate();
+        let to_player = wrapped_delta(pos, player_pos, settings.wraps_world());
+        let step = match stats.ranged {
+            // hold position inside a band around the preferred distance
+            Some(ranged) => {
+                let distance = to_player.length();
+                if distance > ranged.preferred_distance * 1.1 {
+                    1.0
+                } else if distance < ranged.preferred_distance * 0.9 {
+                    -1.0
+                } else {
+                    0.0
+                }
+            }
+            None => 1.0,
+        };
+        // follow the flow field around obstacles, backing off in a straight line
+        let seek = if step > 0.0 {
+            flow_field
+                .direction(pos)
+                .unwrap_or_else(|| to_player.normalize_or_zero())
+        } else {
+            to_player.normalize_or_zero() * step
+        };
+        let steer = seek + avoid_neighbours(&tree, entity, pos, stats.collision_radius, seek);
+        transform.translation +=
+            (steer.clamp_length_max(ENEMY_MAX_STEER) * stats.speed).extend(0.0);
     }
 }
 
-fn spawn_enemies(
+/// Pushes an enemy away from the neighbours it overlaps and sideways around
+/// the ones in its way, in units of the enemy's speed.
+fn avoid_neighbours(
+    tree: &EnemyKdTree,
+    entity: Entity,
+    pos: Vec2,
+    radius: f32,
+    seek: Vec2,
+) -> Vec2 {
+    let mut push = Vec2::ZERO;
+    let mut neighbours = tree.within_radius(pos, radius);
+    neighbours.retain(|other| other.entity != entity);
+    // the closest ones overlap the most, ignore the rest of a dense crowd
+    neighbours.sort_by(|a, b| {
+        a.pos
+            .distance_squared(pos)
+            .total_cmp(&b.pos.distance_squared(pos))
+    });
+    neighbours.truncate(ENEMY_MAX_NEIGHBOURS);
+    for other in neighbours {
+        let away = pos - other.pos;
+        let distance = away.length();
+        let overlap = 1.0 - distance / (radius + other.radius);
+        if overlap <= 0.0 {
+            continue;
+        }
+        // enemies stacked on the exact same spot still need to split up
+        let dir = if distance > 0.0 {
+            away / distance
+        } else {
+            Vec2::from_angle(entity.index() as f32)
+        };
+        push += dir * overlap * ENEMY_SEPARATION_WEIGHT;
+
+        if seek.dot(-away) > 0.0 {
+            let side = if seek.perp_dot(-away) > 0.0 {
+                -seek.perp()
+            } else {
+                seek.perp()
+            };
+            push += side * overlap * ENEMY_AVOIDANCE_WEIGHT;
+        }
+    }
+
+    push
+}
+
+#[allow(clippy::type_complexity)]
+fn fire_enemy_projectiles(
     mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
     handle: Res<GlobalTextureAtlas>,
     player_query: Query<&Transform, With<Player>>,
-    enemy_query: Query<&Transform, (With<Enemy>, Without<Player>)>,
+    mut enemy_query: Query<
+        (Entity, &Transform, &Enemy, &mut EnemyAttackTimer),
+        (Without<Player>, Without<Knockback>),
+    >,
 ) {
-    let num_enemies = enemy_query.iter().len();
-    let enemy_spawn_count = (MAX_NUM_ENEMIES - num_enemies).min(SPAWN_RATE_PER_SECOND);
-
-    if num_enemies >= MAX_NUM_ENEMIES || player_query.is_empty() {
+    if player_query.is_empty() || enemy_query.is_empty() {
         return;
     }
 
     let player_pos = player_query.single().translation.truncate();
-    for _ in 0..enemy_spawn_count {
-        let (x, y) = get_random_position_around(player_pos);
-        commands.spawn((
-            SpriteBundle {
-                texture: handle.image.clone().unwrap(),
-                transform: Transform::from_translation(vec3(x, y, 1.0))
-                    .with_scale(Vec3::splat(SPRITE_SCALE_FACTOR)),
+    for (entity, transform, enemy, mut timer) in enemy_query.iter_mut() {
+        let Some(ranged) = enemy.kind.stats().ranged else {
+            continue;
+        };
+        if !timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        // only shoot from roughly the preferred distance
+        let pos = transform.translation.truncate();
+        let to_player = wrapped_delta(pos, player_pos, settings.wraps_world());
+        if to_player.length() > ranged.preferred_distance * 1.5 {
+            continue;
+        }
+
+        spawn_enemy_projectile(
+            &mut commands,
+            &handle,
+            entity,
+            pos,
+            to_player,
+            &ranged.projectile,
+        );
+    }
+}
+
+pub fn spawn_enemy_projectile(
+    commands: &mut Commands,
+    handle: &GlobalTextureAtlas,
+    source: Entity,
+    pos: Vec2,
+    dir: Vec2,
+    projectile: &EnemyProjectile,
+) {
+    commands.spawn((
+        SpriteBundle {
+            texture: handle.image.clone().unwrap(),
+            sprite: Sprite {
+                color: Color::srgb(1.0, 0.3, 0.3),
                 ..default()
             },
-            TextureAtlas {
-                layout: handle.layout.clone().unwrap(),
-                index: 8,
+            transform: Transform::from_translation(vec3(pos.x, pos.y, 1.0))
+                .with_scale(Vec3::splat(SPRITE_SCALE_FACTOR)),
+            ..default()
+        },
+        TextureAtlas {
+            layout: handle.layout.clone().unwrap(),
+            index: projectile.sprite_index,
+        },
+        Bullet {
+            damage: projectile.damage,
+            kind: DamageKind::Physical,
+            source,
+            knockback: 0.0,
+            speed: projectile.speed,
+            lifetime: Timer::from_seconds(projectile.lifetime, TimerMode::Once),
+        },
+        BulletDirection(dir.normalize_or_zero().extend(0.0)),
+        Faction::Enemy,
+        GameEntity,
+    ));
+}
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    handle: &GlobalTextureAtlas,
+    kind: EnemyKind,
+    pos: Vec2,
+) -> Entity {
+    let stats = kind.stats();
+    let mut enemy = commands.spawn((
+        SpriteBundle {
+            texture: handle.image.clone().unwrap(),
+            sprite: Sprite {
+                color: stats.color,
+                ..default()
             },
-            AnimationTimer(Timer::from_seconds(0.08, TimerMode::Repeating)),
-            Enemy::default(),
-            GameEntity,
-        ));
+            transform: Transform::from_translation(pos.extend(1.0))
+                .with_scale(Vec3::splat(SPRITE_SCALE_FACTOR * stats.size)),
+            ..default()
+        },
+        TextureAtlas {
+            layout: handle.layout.clone().unwrap(),
+            index: stats.first_frame,
+        },
+        AnimationTimer(Timer::from_seconds(stats.frame_time, TimerMode::Repeating)),
+        Enemy { kind },
+        Health(stats.health),
+        GameEntity,
+    ));
+    if stats.armor > 0.0 {
+        enemy.insert(Armor(stats.armor));
+    }
+    if let Some(resistances) = stats.resistances {
+        enemy.insert(resistances);
+    }
+    if let Some(ranged) = stats.ranged {
+        enemy.insert(EnemyAttackTimer(Timer::from_seconds(
+            ranged.fire_interval,
+            TimerMode::Repeating,
+        )));
     }
+
+    enemy.id()
 }
 
-fn get_random_position_around(pos: Vec2) -> (f32, f32) {
+pub fn get_random_position_around(pos: Vec2) -> (f32, f32) {
     let mut rng = rand::thread_rng();
     let angle = rng.gen_range(0.0..PI * 2.0);
     let dist = rng.gen_range(1000.0..2000.0);
@@ -107,10 +492,14 @@ fn get_random_position_around(pos: Vec2) -> (f32, f32) {
     (random_x, random_y)
 }
 
-impl Default for Enemy {
-    fn default() -> Self {
-        Self {
-            health: ENEMEY_HEALTH,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kd_tree_padding_covers_every_kind_in_the_tree() {
+        for kind in EnemyKind::ALL.into_iter().filter(|kind| kind.in_kd_tree()) {
+            assert!(kind.stats().collision_radius <= MAX_KD_TREE_COLLISION_RADIUS);
         }
     }
 }
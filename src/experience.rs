@@ -0,0 +1,478 @@
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use kd_tree::KdTree;
+use rand::seq::SliceRandom;
+
+use crate::collision::Collidable;
+use crate::enemy::EnemyDiedEvent;
+use crate::passive::PassiveWeapons;
+use crate::player::{Player, PlayerUpgrades};
+use crate::state::GameState;
+use crate::weapon::{spawn_weapon, Weapon, WeaponDef, WeaponInventory};
+use crate::world::{wrapped_copies, wrapped_delta, GameEntity};
+use crate::*;
+
+pub struct ExperiencePlugin;
+
+/// Dropped by dead enemies, worth `value` experience once collected. Drops
+/// close to each other are merged into one gem.
+#[derive(Component)]
+pub struct XpGem {
+    pub value: u32,
+    lifetime: Timer,
+}
+
+/// Gems that are lying still, the ones following the player aren't listed.
+#[derive(Resource)]
+pub struct XpGemKdTree(pub KdTree<Collidable>);
+
+/// Set once a gem comes in range, it then follows the player until collected.
+#[derive(Component)]
+pub struct Magnetized;
+
+#[derive(Resource)]
+pub struct Experience {
+    pub level: u32,
+    /// Experience gathered towards the next level.
+    pub xp: u32,
+    pending_level_ups: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Upgrade {
+    Damage,
+    FireRate,
+    MoveSpeed,
+    Blades,
+    Aura,
+    Weapon(Handle<WeaponDef>),
+}
+
+/// The upgrades offered on the current level up screen.
+#[derive(Resource, Default)]
+pub struct UpgradeChoices(pub Vec<Upgrade>);
+
+/// Sent by the level up screen with the index of the picked choice.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct UpgradeChosenEvent(pub usize);
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Experience::default())
+            .insert_resource(XpGemKdTree::default())
+            .init_resource::<UpgradeChoices>()
+            .add_event::<UpgradeChosenEvent>()
+            .add_systems(OnEnter(GameState::GameInit), reset_experience)
+            .add_systems(
+                Update,
+                (
+                    (
+                        spawn_xp_gems,
+                        collect_xp_gems,
+                        despawn_expired_xp_gems,
+                        start_level_up,
+                    )
+                        .chain(),
+                    update_xp_gem_kd_tree.run_if(
+                        on_timer(Duration::from_secs_f32(KD_TREE_REFRESH_RATE))
+                            .or_else(xp_gems_changed),
+                    ),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnEnter(GameState::LevelUp), roll_upgrade_choices)
+            .add_systems(
+                Update,
+                apply_chosen_upgrade.run_if(in_state(GameState::LevelUp)),
+            );
+    }
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            xp: 0,
+            pending_level_ups: 0,
+        }
+    }
+}
+
+impl Default for XpGemKdTree {
+    fn default() -> Self {
+        Self(KdTree::build_by_ordered_float(vec![]))
+    }
+}
+
+impl Experience {
+    pub fn to_next_level(&self) -> u32 {
+        (XP_FIRST_LEVEL * XP_LEVEL_GROWTH.powi(self.level as i32 - 1)).round() as u32
+    }
+
+    /// Fraction of the way to the next level.
+    pub fn progress(&self) -> f32 {
+        self.xp as f32 / self.to_next_level() as f32
+    }
+
+    fn add(&mut self, xp: u32) {
+        self.xp += xp;
+        while self.xp >= self.to_next_level() {
+            self.xp -= self.to_next_level();
+            self.level += 1;
+            self.pending_level_ups += 1;
+        }
+    }
+}
+
+impl Upgrade {
+    pub fn title(&self) -> String {
+        match self {
+            Upgrade::Damage => "Damage".to_string(),
+            Upgrade::FireRate => "Fire Rate".to_string(),
+            Upgrade::MoveSpeed => "Move Speed".to_string(),
+            Upgrade::Blades => "Orbiting Blades".to_string(),
+            Upgrade::Aura => "Damage Aura".to_string(),
+            Upgrade::Weapon(def) => weapon_name(def),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        let percent = |step: f32| (step * 100.0).round();
+        match self {
+            Upgrade::Damage => format!("+{}% weapon damage", percent(UPGRADE_DAMAGE_STEP)),
+            Upgrade::FireRate => format!("+{}% fire rate", percent(UPGRADE_FIRE_RATE_STEP)),
+            Upgrade::MoveSpeed => format!("+{}% move speed", percent(UPGRADE_MOVE_SPEED_STEP)),
+            Upgrade::Blades => "More and sharper blades".to_string(),
+            Upgrade::Aura => "Wider and stronger aura".to_string(),
+            Upgrade::Weapon(_) => "New weapon".to_string(),
+        }
+    }
+}
+
+/// A readable name from the weapon's file, `grenade_launcher.weapon.ron`
+/// becomes "Grenade Launcher".
+fn weapon_name(def: &Handle<WeaponDef>) -> String {
+    let Some(name) = def
+        .path()
+        .and_then(|path| path.path().file_name())
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+    else {
+        return "Weapon".to_string();
+    };
+
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| {
+                first.to_uppercase().chain(chars).collect()
+            })
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn reset_experience(mut experience: ResMut<Experience>) {
+    *experience = Experience::default();
+}
+
+fn spawn_xp_gems(
+    mut commands: Commands,
+    tree: Res<XpGemKdTree>,
+    mut gem_query: Query<&mut XpGem, Without<Magnetized>>,
+    mut events: EventReader<EnemyDiedEvent>,
+) {
+    // gems dropped this frame aren't in the tree yet, they're merged here
+    let mut dropped: Vec<(Vec2, u32)> = Vec::new();
+    for event in events.read() {
+        let value = event.kind.stats().xp;
+        let nearby = tree
+            .0
+            .within_radius(&[event.pos.x, event.pos.y], XP_GEM_MERGE_RADIUS)
+            .into_iter()
+            .find(|gem| gem_query.contains(gem.entity));
+        if let Some(mut gem) = nearby.and_then(|gem| gem_query.get_mut(gem.entity).ok()) {
+            gem.value += value;
+            gem.lifetime.reset();
+            continue;
+        }
+        match dropped
+            .iter_mut()
+            .find(|(pos, _)| pos.distance(event.pos) <= XP_GEM_MERGE_RADIUS)
+        {
+            Some((_, total)) => *total += value,
+            None => dropped.push((event.pos, value)),
+        }
+    }
+
+    for (pos, value) in dropped {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(0.2, 0.8, 0.9),
+                    custom_size: Some(Vec2::splat(XP_GEM_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(1.0))
+                    .with_rotation(Quat::from_rotation_z(PI / 4.0)),
+                ..default()
+            },
+            XpGem {
+                value,
+                lifetime: Timer::from_seconds(XP_GEM_LIFETIME_SECS, TimerMode::Once),
+            },
+            GameEntity,
+        ));
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn collect_xp_gems(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    tree: Res<XpGemKdTree>,
+    mut experience: ResMut<Experience>,
+    player_query: Query<&Transform, With<Player>>,
+    idle_query: Query<(), (With<XpGem>, Without<Magnetized>)>,
+    mut gem_query: Query<(Entity, &mut Transform, &XpGem), (With<Magnetized>, Without<Player>)>,
+) {
+    if player_query.is_empty() {
+        return;
+    }
+
+    // only the gems in reach start moving, the rest stay where they dropped
+    let player_pos = player_query.single().translation.truncate();
+    for gem in tree
+        .0
+        .within_radius(&[player_pos.x, player_pos.y], XP_MAGNET_RADIUS)
+    {
+        if idle_query.contains(gem.entity) {
+            commands.entity(gem.entity).insert(Magnetized);
+        }
+    }
+
+    for (entity, mut transform, gem) in gem_query.iter_mut() {
+        let to_player = wrapped_delta(
+            transform.translation.truncate(),
+            player_pos,
+            settings.wraps_world(),
+        );
+        let distance = to_player.length();
+        if distance <= PLAYER_COLLISION_RADIUS {
+            experience.add(gem.value);
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let step = (XP_GEM_SPEED * time.delta_seconds()).min(distance);
+        transform.translation += (to_player / distance * step).extend(0.0);
+    }
+}
+
+fn despawn_expired_xp_gems(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut gem_query: Query<(Entity, &mut XpGem), Without<Magnetized>>,
+) {
+    for (entity, mut gem) in gem_query.iter_mut() {
+        if gem.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Rebuilds the tree early when gems drop or get picked up, so new drops can
+/// be merged into right away.
+fn xp_gems_changed(
+    added_query: Query<(), Added<XpGem>>,
+    mut removed: RemovedComponents<XpGem>,
+) -> bool {
+    // read every removal so they don't trigger another rebuild next frame
+    removed.read().count() > 0 || !added_query.is_empty()
+}
+
+#[allow(clippy::type_complexity)]
+fn update_xp_gem_kd_tree(
+    settings: Res<Settings>,
+    mut tree: ResMut<XpGemKdTree>,
+    gem_query: Query<(&Transform, Entity), (With<XpGem>, Without<Magnetized>)>,
+) {
+    let mut gems = Vec::new();
+    for (transform, entity) in gem_query.iter() {
+        let pos = transform.translation.truncate();
+        let copies = if settings.wraps_world() {
+            wrapped_copies(pos, XP_MAGNET_RADIUS)
+        } else {
+            Vec::new()
+        };
+        for pos in std::iter::once(pos).chain(copies) {
+            gems.push(Collidable {
+                entity,
+                pos,
+                radius: XP_GEM_SIZE / 2.0,
+            });
+        }
+    }
+
+    tree.0 = KdTree::build_by_ordered_float(gems);
+}
+
+fn start_level_up(experience: Res<Experience>, mut next_state: ResMut<NextState<GameState>>) {
+    if experience.pending_level_ups > 0 {
+        next_state.set(GameState::LevelUp);
+    }
+}
+
+/// Picks distinct upgrades at random, weapons are only offered while there's
+/// a free slot and the player doesn't have them yet.
+fn roll_upgrades(
+    weapon_defs: &GlobalWeaponDefs,
+    owned: &[AssetId<WeaponDef>],
+    free_slots: bool,
+) -> Vec<Upgrade> {
+    let mut pool = vec![
+        Upgrade::Damage,
+        Upgrade::FireRate,
+        Upgrade::MoveSpeed,
+        Upgrade::Blades,
+        Upgrade::Aura,
+    ];
+    if free_slots {
+        pool.extend(
+            weapon_defs
+                .unlockable_weapons
+                .iter()
+                .filter(|def| !owned.contains(&def.id()))
+                .map(|def| Upgrade::Weapon(def.clone())),
+        );
+    }
+
+    pool.choose_multiple(&mut rand::thread_rng(), UPGRADE_CHOICES)
+        .cloned()
+        .collect()
+}
+
+fn roll_upgrade_choices(
+    mut choices: ResMut<UpgradeChoices>,
+    weapon_defs: Res<GlobalWeaponDefs>,
+    player_query: Query<&WeaponInventory, With<Player>>,
+    weapon_query: Query<&Weapon>,
+) {
+    let Ok(inventory) = player_query.get_single() else {
+        choices.0 = roll_upgrades(&weapon_defs, &[], false);
+        return;
+    };
+    let owned: Vec<_> = weapon_query
+        .iter_many(&inventory.slots)
+        .map(|weapon| weapon.0.id())
+        .collect();
+    choices.0 = roll_upgrades(
+        &weapon_defs,
+        &owned,
+        inventory.slots.len() < MAX_WEAPON_SLOTS,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_chosen_upgrade(
+    mut commands: Commands,
+    handle: Res<GlobalTextureAtlas>,
+    weapon_defs: Res<GlobalWeaponDefs>,
+    mut events: EventReader<UpgradeChosenEvent>,
+    mut choices: ResMut<UpgradeChoices>,
+    mut experience: ResMut<Experience>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut player_query: Query<
+        (
+            &mut PlayerUpgrades,
+            &mut PassiveWeapons,
+            &mut WeaponInventory,
+        ),
+        With<Player>,
+    >,
+    weapon_query: Query<&Weapon>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    let Some(upgrade) = choices.0.get(event.0).cloned() else {
+        return;
+    };
+    if player_query.is_empty() {
+        return;
+    }
+
+    let (mut upgrades, mut passive, mut inventory) = player_query.single_mut();
+    let mut owned: Vec<_> = weapon_query
+        .iter_many(&inventory.slots)
+        .map(|weapon| weapon.0.id())
+        .collect();
+    info!("upgrade picked: {}", upgrade.title());
+    match upgrade {
+        Upgrade::Damage => upgrades.damage += UPGRADE_DAMAGE_STEP,
+        Upgrade::FireRate => upgrades.fire_rate += UPGRADE_FIRE_RATE_STEP,
+        Upgrade::MoveSpeed => upgrades.move_speed += UPGRADE_MOVE_SPEED_STEP,
+        Upgrade::Blades => passive.blade_level += 1,
+        Upgrade::Aura => passive.aura_level += 1,
+        Upgrade::Weapon(def) => {
+            owned.push(def.id());
+            let weapon = spawn_weapon(&mut commands, &handle, def, false);
+            inventory.slots.push(weapon);
+        }
+    }
+
+    experience.pending_level_ups -= 1;
+    if experience.pending_level_ups == 0 {
+        next_state.set(GameState::InGame);
+        return;
+    }
+    // several levels at once get one pick each, roll again for the next
+    choices.0 = roll_upgrades(
+        &weapon_defs,
+        &owned,
+        inventory.slots.len() < MAX_WEAPON_SLOTS,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_gains_fill_the_bar() {
+        let mut experience = Experience::default();
+        experience.add(2);
+        assert_eq!((experience.level, experience.xp), (1, 2));
+        assert_eq!(experience.pending_level_ups, 0);
+        assert!((experience.progress() - 2.0 / XP_FIRST_LEVEL).abs() < 1e-6);
+    }
+
+    #[test]
+    fn one_gain_can_cross_several_levels() {
+        let mut experience = Experience::default();
+        let mut needed = 0;
+        for level in 1..=3 {
+            experience.level = level;
+            needed += experience.to_next_level();
+        }
+        experience.level = 1;
+
+        experience.add(needed + 2);
+        assert_eq!(experience.level, 4);
+        assert_eq!(experience.xp, 2);
+        assert_eq!(experience.pending_level_ups, 3);
+        assert!(experience.xp < experience.to_next_level());
+    }
+
+    #[test]
+    fn exact_gain_levels_up_with_nothing_left() {
+        let mut experience = Experience::default();
+        experience.add(experience.to_next_level());
+        assert_eq!((experience.level, experience.xp), (2, 0));
+        assert_eq!(experience.pending_level_ups, 1);
+    }
+}
@@ -0,0 +1,300 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bevy::math::vec2;
+use bevy::prelude::*;
+
+use crate::player::Player;
+use crate::state::GameState;
+use crate::world::{wrap_to_world, wrapped_delta, Obstacle};
+use crate::*;
+
+pub struct FlowFieldPlugin;
+
+/// Path costs towards the player over a grid covering the world, or a
+/// world-sized window around the player on an endless map. Enemies follow the
+/// cost gradient, so a single field serves every enemy.
+///
+/// Rebuilds run a few thousand cells per frame while enemies keep sampling the
+/// last finished field.
+#[derive(Resource)]
+pub struct FlowField {
+    columns: usize,
+    rows: usize,
+    /// Bottom left corner of the grid.
+    origin: Vec2,
+    /// Cells on opposite edges are neighbours in a wrapping arena.
+    wrap: bool,
+    /// Keeps the grid centred on the player instead of fixed over the world.
+    follow: bool,
+    blocked: Vec<bool>,
+    costs: Vec<u32>,
+    target: Option<usize>,
+    build: Option<FieldBuild>,
+}
+
+struct FieldBuild {
+    target: usize,
+    costs: Vec<u32>,
+    open: BinaryHeap<Reverse<(u32, usize)>>,
+}
+
+const UNREACHABLE: u32 = u32::MAX;
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+impl Plugin for FlowFieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FlowField::default())
+            .add_systems(OnEnter(GameState::GameInit), reset_flow_field)
+            .add_systems(
+                Update,
+                (block_obstacles, update_flow_field)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+impl Default for FlowField {
+    fn default() -> Self {
+        let columns = (2.0 * WORLD_WIDTH / FLOW_FIELD_CELL_SIZE).ceil() as usize;
+        let rows = (2.0 * WORLD_HEIGHT / FLOW_FIELD_CELL_SIZE).ceil() as usize;
+        Self {
+            columns,
+            rows,
+            origin: -vec2(WORLD_WIDTH, WORLD_HEIGHT),
+            wrap: false,
+            follow: false,
+            blocked: vec![false; columns * rows],
+            costs: vec![UNREACHABLE; columns * rows],
+            target: None,
+            build: None,
+        }
+    }
+}
+
+impl FlowField {
+    /// Marks every cell touching the rectangle as impassable.
+    pub fn block_rect(&mut self, min: Vec2, max: Vec2) {
+        let grid_max = self.origin + self.size();
+        if max.cmplt(self.origin).any() || min.cmpgt(grid_max).any() {
+            return;
+        }
+        let (Some((x0, y0)), Some((x1, y1))) = (self.clamped_cell(min), self.clamped_cell(max))
+        else {
+            return;
+        };
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                self.blocked[y * self.columns + x] = true;
+            }
+        }
+        // force a rebuild around the new obstacle
+        self.target = None;
+        self.build = None;
+    }
+
+    pub fn clear_blocked(&mut self) {
+        self.blocked.fill(false);
+        self.target = None;
+        self.build = None;
+    }
+
+    pub fn is_blocked(&self, pos: Vec2) -> bool {
+        self.cell_index(pos).is_some_and(|i| self.blocked[i])
+    }
+
+    /// The direction to move from `pos` towards the player, or `None` when
+    /// `pos` is outside the field, unreachable or already in the player's cell.
+    pub fn direction(&self, pos: Vec2) -> Option<Vec2> {
+        let index = self.cell_index(pos)?;
+        let cost = self.costs[index];
+        if cost == UNREACHABLE || cost == 0 {
+            return None;
+        }
+
+        let (x, y) = (index % self.columns, index / self.columns);
+        let best = self
+            .neighbours(x, y)
+            .map(|(neighbour, _)| neighbour)
+            .min_by_key(|neighbour| self.costs[*neighbour])
+            .filter(|neighbour| self.costs[*neighbour] < cost)?;
+        Some(wrapped_delta(pos, self.cell_center(best), self.wrap).normalize_or_zero())
+    }
+
+    fn size(&self) -> Vec2 {
+        vec2(self.columns as f32, self.rows as f32) * FLOW_FIELD_CELL_SIZE
+    }
+
+    /// Moves a following grid back over `pos` once `pos` strays a quarter of
+    /// the way towards its edge. Returns whether it moved, every obstacle then
+    /// has to be blocked again.
+    fn recentre(&mut self, pos: Vec2) -> bool {
+        let size = self.size();
+        let offset = (pos - (self.origin + size / 2.0)).abs();
+        if !self.follow || (offset.x < size.x / 4.0 && offset.y < size.y / 4.0) {
+            return false;
+        }
+
+        self.origin = ((pos - size / 2.0) / FLOW_FIELD_CELL_SIZE).floor() * FLOW_FIELD_CELL_SIZE;
+        self.clear_blocked();
+        true
+    }
+
+    fn cell_index(&self, pos: Vec2) -> Option<usize> {
+        let pos = if self.wrap { wrap_to_world(pos) } else { pos };
+        let x = ((pos.x - self.origin.x) / FLOW_FIELD_CELL_SIZE).floor();
+        let y = ((pos.y - self.origin.y) / FLOW_FIELD_CELL_SIZE).floor();
+        if x < 0.0 || y < 0.0 || x >= self.columns as f32 || y >= self.rows as f32 {
+            return None;
+        }
+
+        Some(y as usize * self.columns + x as usize)
+    }
+
+    fn clamped_cell(&self, pos: Vec2) -> Option<(usize, usize)> {
+        if self.columns == 0 || self.rows == 0 {
+            return None;
+        }
+        let x = ((pos.x - self.origin.x) / FLOW_FIELD_CELL_SIZE).floor();
+        let y = ((pos.y - self.origin.y) / FLOW_FIELD_CELL_SIZE).floor();
+        Some((
+            (x.max(0.0) as usize).min(self.columns - 1),
+            (y.max(0.0) as usize).min(self.rows - 1),
+        ))
+    }
+
+    fn cell_center(&self, index: usize) -> Vec2 {
+        let (x, y) = (index % self.columns, index / self.columns);
+        self.origin + (vec2(x as f32, y as f32) + 0.5) * FLOW_FIELD_CELL_SIZE
+    }
+
+    /// Open neighbours of a cell and the cost of stepping to each. Diagonal
+    /// steps can't cut the corner of a blocked cell.
+    fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+        NEIGHBOURS.iter().filter_map(move |&(dx, dy)| {
+            let (columns, rows) = (self.columns as i32, self.rows as i32);
+            let (mut nx, mut ny) = (x as i32 + dx, y as i32 + dy);
+            if self.wrap {
+                nx = nx.rem_euclid(columns);
+                ny = ny.rem_euclid(rows);
+            }
+            if nx < 0 || ny < 0 || nx >= self.columns as i32 || ny >= self.rows as i32 {
+                return None;
+            }
+            let open = |cx: i32, cy: i32| !self.blocked[cy as usize * self.columns + cx as usize];
+            if !open(nx, ny) {
+                return None;
+            }
+            if dx != 0 && dy != 0 {
+                if !open(nx, y as i32) || !open(x as i32, ny) {
+                    return None;
+                }
+                return Some((ny as usize * self.columns + nx as usize, DIAGONAL_COST));
+            }
+            Some((ny as usize * self.columns + nx as usize, STRAIGHT_COST))
+        })
+    }
+
+    fn start_build(&mut self, target: usize) {
+        let mut costs = vec![UNREACHABLE; self.columns * self.rows];
+        let mut open = BinaryHeap::new();
+        costs[target] = 0;
+        open.push(Reverse((0, target)));
+        self.build = Some(FieldBuild {
+            target,
+            costs,
+            open,
+        });
+    }
+
+    /// Runs up to `budget` steps of the pending rebuild, replacing the live
+    /// field once it finishes.
+    fn continue_build(&mut self, budget: usize) {
+        let Some(mut build) = self.build.take() else {
+            return;
+        };
+
+        for _ in 0..budget {
+            let Some(Reverse((cost, index))) = build.open.pop() else {
+                self.costs = build.costs;
+                self.target = Some(build.target);
+                return;
+            };
+            if cost > build.costs[index] {
+                continue;
+            }
+            let (x, y) = (index % self.columns, index / self.columns);
+            for (neighbour, step) in self.neighbours(x, y) {
+                let next = cost + step;
+                if next < build.costs[neighbour] {
+                    build.costs[neighbour] = next;
+                    build.open.push(Reverse((next, neighbour)));
+                }
+            }
+        }
+
+        self.build = Some(build);
+    }
+}
+
+fn reset_flow_field(mut flow_field: ResMut<FlowField>, settings: Res<Settings>) {
+    *flow_field = FlowField {
+        wrap: settings.wraps_world(),
+        follow: settings.world_edges() == WorldEdges::Endless,
+        ..default()
+    };
+}
+
+fn block_obstacles(
+    mut flow_field: ResMut<FlowField>,
+    player_query: Query<&Transform, With<Player>>,
+    obstacle_query: Query<(&Transform, Ref<Obstacle>)>,
+) {
+    // cells stay blocked when obstacles stream out, the same ones come back
+    // from the seed, so only new ones need blocking unless the grid moved
+    let recentred = player_query
+        .get_single()
+        .is_ok_and(|transform| flow_field.recentre(transform.translation.truncate()));
+    for (transform, obstacle) in obstacle_query.iter() {
+        if !recentred && !obstacle.is_added() {
+            continue;
+        }
+        let center = transform.translation.truncate();
+        let half_size = obstacle.half_size + Vec2::splat(FLOW_FIELD_OBSTACLE_MARGIN);
+        flow_field.block_rect(center - half_size, center + half_size);
+    }
+}
+
+fn update_flow_field(
+    mut flow_field: ResMut<FlowField>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    if player_query.is_empty() {
+        return;
+    }
+
+    let player_pos = player_query.single().translation.truncate();
+    let Some((x, y)) = flow_field.clamped_cell(player_pos) else {
+        return;
+    };
+    let target = y * flow_field.columns + x;
+
+    // finish the running build before chasing a newer player cell, so a
+    // moving player can't starve it
+    if flow_field.build.is_none() && flow_field.target != Some(target) {
+        flow_field.start_build(target);
+    }
+    flow_field.continue_build(FLOW_FIELD_BUILD_BUDGET);
+}
@@ -3,10 +3,18 @@ use bevy::{
     prelude::*,
 };
 
+use crate::arena::Arena;
+use crate::boss::Boss;
+use crate::damage::Health;
+use crate::director::{WaveDirector, WavePhase, WaveSchedule};
 use crate::enemy::Enemy;
-use crate::player::{Health, Player};
+use crate::experience::{Experience, UpgradeChoices, UpgradeChosenEvent};
+use crate::pickup::{PickupKind, PowerUps};
+use crate::player::Player;
 use crate::state::GameState;
-use crate::world::GameEntity;
+use crate::weapon::{ActiveWeapon, Ammo, Reloading};
+use crate::world::{GameEntity, WorldSeed};
+use crate::{GlobalArenas, Settings, WorldEdges};
 
 pub struct GuiPlugin;
 
@@ -14,6 +22,33 @@ pub struct GuiPlugin;
 struct DebugText;
 #[derive(Component)]
 struct MainMenuItem;
+#[derive(Component, Clone, Copy)]
+enum MainMenuButton {
+    Play,
+    ToggleDirector,
+    CycleEdges,
+    CycleMap,
+}
+#[derive(Component)]
+struct MainMenuButtonText(MainMenuButton);
+#[derive(Component)]
+struct BossHealthBar;
+#[derive(Component)]
+struct BossHealthBarFill;
+#[derive(Component)]
+struct XpBarFill;
+#[derive(Component)]
+struct XpLevelText;
+#[derive(Component)]
+struct LevelUpMenu;
+#[derive(Component, Clone, Copy)]
+struct UpgradeCard(usize);
+#[derive(Component)]
+struct PowerUpHud;
+#[derive(Component)]
+struct PowerUpCountdown(PickupKind);
+
+const UPGRADE_CARD_KEYS: [KeyCode; 3] = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3];
 
 impl Plugin for GuiPlugin {
     fn build(&self, app: &mut App) {
@@ -24,15 +59,42 @@ impl Plugin for GuiPlugin {
                 Update,
                 handle_main_menu_buttons.run_if(in_state(GameState::MainMenu)),
             )
-            .add_systems(OnEnter(GameState::GameInit), spawn_debug_text)
+            .add_systems(
+                OnEnter(GameState::GameInit),
+                (
+                    spawn_debug_text,
+                    spawn_boss_health_bar,
+                    spawn_xp_bar,
+                    spawn_power_up_hud,
+                ),
+            )
             .add_systems(
                 Update,
-                update_debug_text.run_if(in_state(GameState::InGame)),
-            );
+                (
+                    update_debug_text,
+                    update_boss_health_bar,
+                    update_xp_bar,
+                    update_power_up_hud,
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (refresh_level_up_menu, handle_upgrade_card_input)
+                    .chain()
+                    .run_if(in_state(GameState::LevelUp)),
+            )
+            .add_systems(OnExit(GameState::LevelUp), despawn_level_up_menu);
     }
 }
 
-fn setup_main_menu(mut commands: Commands) {
+fn setup_main_menu(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    global_arenas: Res<GlobalArenas>,
+    arenas: Res<Assets<Arena>>,
+) {
+    let map = map_name(&settings, &global_arenas, &arenas);
     commands
         .spawn(NodeBundle {
             style: Style {
@@ -40,38 +102,96 @@ fn setup_main_menu(mut commands: Commands) {
                 height: Val::Percent(100.0),
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(15.0),
                 ..default()
             },
             ..default()
         })
         .with_children(|parent| {
-            parent
-                .spawn(ButtonBundle {
-                    style: Style {
-                        width: Val::Px(150.0),
-                        height: Val::Px(65.0),
-                        border: UiRect::all(Val::Px(5.0)),
-                        justify_content: JustifyContent::Center,
-                        align_items: AlignItems::Center,
-                        ..default()
-                    },
-                    border_color: BorderColor(Color::BLACK),
-                    ..default()
-                })
-                .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section(
-                        "Play",
-                        TextStyle {
-                            font_size: 40.0,
-                            color: Color::BLACK,
-                            ..default()
-                        },
-                    ));
-                });
+            for (button, width) in [
+                (MainMenuButton::Play, 150.0),
+                (MainMenuButton::ToggleDirector, 320.0),
+                (MainMenuButton::CycleEdges, 320.0),
+                (MainMenuButton::CycleMap, 420.0),
+            ] {
+                let label = main_menu_label(button, &settings, &map);
+                spawn_main_menu_button(parent, button, width, label);
+            }
         })
         .insert(MainMenuItem);
 }
 
+fn spawn_main_menu_button(
+    parent: &mut ChildBuilder,
+    button: MainMenuButton,
+    width: f32,
+    label: String,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(width),
+                    height: Val::Px(65.0),
+                    border: UiRect::all(Val::Px(5.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: BorderColor(Color::BLACK),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 40.0,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                ),
+                MainMenuButtonText(button),
+            ));
+        });
+}
+
+fn main_menu_label(button: MainMenuButton, settings: &Settings, map: &str) -> String {
+    match button {
+        MainMenuButton::Play => "Play".to_string(),
+        MainMenuButton::ToggleDirector => {
+            let state = if settings.adaptive_director {
+                "On"
+            } else {
+                "Off"
+            };
+            format!("Director: {state}")
+        }
+        MainMenuButton::CycleEdges => {
+            let state = match settings.edges {
+                WorldEdges::Walled => "Walled",
+                WorldEdges::Wrap => "Wrap",
+                WorldEdges::Endless => "Endless",
+            };
+            format!("Edges: {state}")
+        }
+        MainMenuButton::CycleMap => format!("Map: {map}"),
+    }
+}
+
+fn map_name(settings: &Settings, global_arenas: &GlobalArenas, arenas: &Assets<Arena>) -> String {
+    let Some(index) = settings.arena else {
+        return "Generated".to_string();
+    };
+    global_arenas.selected(settings, arenas).map_or_else(
+        || format!("Arena {}", index + 1),
+        |arena| arena.name.clone(),
+    )
+}
+
 fn spawn_debug_text(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands
         .spawn((
@@ -93,7 +213,7 @@ fn spawn_debug_text(mut commands: Commands, asset_server: Res<AssetServer>) {
                 .spawn(NodeBundle {
                     style: Style {
                         width: Val::Px(345.0),
-                        height: Val::Px(125.0),
+                        height: Val::Px(230.0),
                         align_items: AlignItems::Center,
                         flex_direction: FlexDirection::Column,
                         justify_content: JustifyContent::Center,
@@ -120,11 +240,363 @@ fn spawn_debug_text(mut commands: Commands, asset_server: Res<AssetServer>) {
         });
 }
 
+fn spawn_boss_health_bar(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    top: Val::Px(10.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            BossHealthBar,
+            GameEntity,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(50.0),
+                        height: Val::Px(24.0),
+                        border: UiRect::all(Val::Px(3.0)),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    background_color: BackgroundColor::from(Color::BLACK.with_alpha(0.9)),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: BackgroundColor::from(Color::srgb(0.8, 0.1, 0.1)),
+                            ..default()
+                        },
+                        BossHealthBarFill,
+                    ));
+                });
+        });
+}
+
+fn spawn_xp_bar(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    bottom: Val::Px(10.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            GameEntity,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "Lv 1",
+                    TextStyle {
+                        font: asset_server.load("monogram.ttf"),
+                        font_size: 32.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                XpLevelText,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(60.0),
+                        height: Val::Px(16.0),
+                        border: UiRect::all(Val::Px(3.0)),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    background_color: BackgroundColor::from(Color::BLACK.with_alpha(0.9)),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: BackgroundColor::from(Color::srgb(0.2, 0.8, 0.9)),
+                            ..default()
+                        },
+                        XpBarFill,
+                    ));
+                });
+        });
+}
+
+fn update_xp_bar(
+    experience: Res<Experience>,
+    mut fill_query: Query<&mut Style, With<XpBarFill>>,
+    mut text_query: Query<&mut Text, With<XpLevelText>>,
+) {
+    if fill_query.is_empty() || text_query.is_empty() {
+        return;
+    }
+
+    fill_query.single_mut().width = Val::Percent(experience.progress().clamp(0.0, 1.0) * 100.0);
+    text_query.single_mut().sections[0].value = format!("Lv {}", experience.level);
+}
+
+fn spawn_power_up_hud(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(10.0),
+                bottom: Val::Px(50.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            ..default()
+        },
+        PowerUpHud,
+        GameEntity,
+    ));
+}
+
+/// One icon and countdown per active power up, rebuilt when one starts or
+/// runs out.
+fn update_power_up_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    power_ups: Res<PowerUps>,
+    hud_query: Query<Entity, With<PowerUpHud>>,
+    mut countdown_query: Query<(&mut Text, &PowerUpCountdown)>,
+) {
+    if hud_query.is_empty() {
+        return;
+    }
+
+    let shown: Vec<_> = countdown_query
+        .iter()
+        .map(|(_, countdown)| countdown.0)
+        .collect();
+    let active: Vec<_> = power_ups.0.iter().map(|power_up| power_up.kind).collect();
+    if shown != active {
+        let hud = hud_query.single();
+        commands.entity(hud).despawn_descendants();
+        commands.entity(hud).with_children(|parent| {
+            for kind in active {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(8.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(20.0),
+                                height: Val::Px(20.0),
+                                border: UiRect::all(Val::Px(2.0)),
+                                ..default()
+                            },
+                            border_color: BorderColor(Color::BLACK),
+                            background_color: BackgroundColor::from(kind.color()),
+                            ..default()
+                        });
+                        parent.spawn((
+                            TextBundle::from_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server.load("monogram.ttf"),
+                                    font_size: 28.0,
+                                    color: Color::WHITE,
+                                },
+                            ),
+                            PowerUpCountdown(kind),
+                        ));
+                    });
+            }
+        });
+        return;
+    }
+
+    for (mut text, countdown) in countdown_query.iter_mut() {
+        let Some(power_up) = power_ups.0.iter().find(|p| p.kind == countdown.0) else {
+            continue;
+        };
+        text.sections[0].value = format!(
+            "{} {:.0}s",
+            countdown.0.name(),
+            power_up.timer.remaining_secs().ceil()
+        );
+    }
+}
+
+/// Rebuilds the cards whenever a new set of upgrades is rolled.
+fn refresh_level_up_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    choices: Res<UpgradeChoices>,
+    experience: Res<Experience>,
+    menu_query: Query<Entity, With<LevelUpMenu>>,
+) {
+    if !choices.is_changed() && !menu_query.is_empty() {
+        return;
+    }
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let font = asset_server.load("monogram.ttf");
+    let text_style = |font_size: f32| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(20.0),
+                    ..default()
+                },
+                background_color: BackgroundColor::from(Color::BLACK.with_alpha(0.6)),
+                ..default()
+            },
+            LevelUpMenu,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                format!("Level {}!", experience.level),
+                text_style(64.0),
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (index, upgrade) in choices.0.iter().enumerate() {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        width: Val::Px(260.0),
+                                        height: Val::Px(180.0),
+                                        border: UiRect::all(Val::Px(5.0)),
+                                        padding: UiRect::all(Val::Px(10.0)),
+                                        flex_direction: FlexDirection::Column,
+                                        justify_content: JustifyContent::SpaceBetween,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    border_color: BorderColor(Color::WHITE),
+                                    background_color: BackgroundColor::from(
+                                        Color::BLACK.with_alpha(0.9),
+                                    ),
+                                    ..default()
+                                },
+                                UpgradeCard(index),
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    upgrade.title(),
+                                    text_style(40.0),
+                                ));
+                                parent.spawn(TextBundle::from_section(
+                                    upgrade.description(),
+                                    text_style(28.0),
+                                ));
+                                parent.spawn(TextBundle::from_section(
+                                    format!("[{}]", index + 1),
+                                    text_style(28.0),
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+fn handle_upgrade_card_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    interaction_query: Query<(&Interaction, &UpgradeCard), Changed<Interaction>>,
+    mut events: EventWriter<UpgradeChosenEvent>,
+) {
+    for (interaction, card) in interaction_query.iter() {
+        if interaction == &Interaction::Pressed {
+            events.send(UpgradeChosenEvent(card.0));
+            return;
+        }
+    }
+    if let Some(index) = UPGRADE_CARD_KEYS
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key))
+    {
+        events.send(UpgradeChosenEvent(index));
+    }
+}
+
+fn despawn_level_up_menu(mut commands: Commands, menu_query: Query<Entity, With<LevelUpMenu>>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn update_boss_health_bar(
+    boss_query: Query<(&Health, &Enemy), With<Boss>>,
+    mut bar_query: Query<&mut Visibility, With<BossHealthBar>>,
+    mut fill_query: Query<&mut Style, With<BossHealthBarFill>>,
+) {
+    if bar_query.is_empty() || fill_query.is_empty() {
+        return;
+    }
+
+    let mut visibility = bar_query.single_mut();
+    let Ok((health, enemy)) = boss_query.get_single() else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    *visibility = Visibility::Inherited;
+    let fraction = (health.0 / enemy.kind.stats().health).clamp(0.0, 1.0);
+    fill_query.single_mut().width = Val::Percent(fraction * 100.0);
+}
+
+#[allow(clippy::too_many_arguments)]
 fn update_debug_text(
     mut query: Query<&mut Text, With<DebugText>>,
     diagnostics: Res<DiagnosticsStore>,
     enemy_query: Query<(), With<Enemy>>,
     player_query: Query<&Health, With<Player>>,
+    weapon_query: Query<(&Ammo, Option<&Reloading>), With<ActiveWeapon>>,
+    director: Res<WaveDirector>,
+    schedules: Res<Assets<WaveSchedule>>,
+    seed: Res<WorldSeed>,
 ) {
     if query.is_empty() {
         return;
@@ -132,25 +604,77 @@ fn update_debug_text(
 
     let num_enemies = enemy_query.iter().count();
     let player_health = player_query.single().0;
+    let ammo = match weapon_query.get_single() {
+        Ok((_, Some(reloading))) => format!("reloading {:.1}s", reloading.remaining_secs()),
+        Ok((ammo, None)) => format!("{}/{}", ammo.magazine, ammo.reserve),
+        Err(_) => "-".to_string(),
+    };
+    let remaining = schedules
+        .get(&director.schedule)
+        .map_or(0.0, |schedule| director.remaining(schedule));
+    let wave = match director.phase {
+        WavePhase::Waiting => "-".to_string(),
+        WavePhase::Wave => format!("{} ({remaining:.0}s)", director.wave_number),
+        WavePhase::Intermission => format!("next in {remaining:.0}s"),
+    };
     let mut text = query.single_mut();
     if let Some(fps) = diagnostics.get(&FrameTimeDiagnosticsPlugin::FPS) {
         if let Some(value) = fps.smoothed() {
-            text.sections[0].value =
-                format!("FPS: {value:.2}\nEnemies: {num_enemies}\nHealth: {player_health}");
+            text.sections[0].value = format!(
+                "FPS: {value:.2}\nEnemies: {num_enemies}\nHealth: {player_health}\nAmmo: {ammo}\nWave: {wave}\nSeed: {}",
+                seed.0,
+            );
         }
     }
 }
 
 fn handle_main_menu_buttons(
-    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    interaction_query: Query<(&Interaction, &MainMenuButton), Changed<Interaction>>,
+    mut text_query: Query<(&mut Text, &MainMenuButtonText)>,
+    mut settings: ResMut<Settings>,
+    asset_server: Res<AssetServer>,
+    global_arenas: Res<GlobalArenas>,
+    arenas: Res<Assets<Arena>>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
-    for interaction in interaction_query.iter() {
-        match interaction {
-            Interaction::Pressed => {
+    for (interaction, button) in interaction_query.iter() {
+        if interaction != &Interaction::Pressed {
+            continue;
+        }
+        match button {
+            MainMenuButton::Play => {
+                // an arena that's still loading, or failed to, can't be played
+                let handle = settings
+                    .arena
+                    .and_then(|index| global_arenas.arenas.get(index));
+                if handle.is_some_and(|handle| !asset_server.is_loaded_with_dependencies(handle)) {
+                    warn!("selected arena isn't loaded yet");
+                    continue;
+                }
                 next_state.set(GameState::GameInit);
             }
-            _ => {}
+            MainMenuButton::ToggleDirector => {
+                settings.adaptive_director = !settings.adaptive_director;
+            }
+            MainMenuButton::CycleEdges => {
+                settings.edges = match settings.edges {
+                    WorldEdges::Walled => WorldEdges::Wrap,
+                    WorldEdges::Wrap => WorldEdges::Endless,
+                    WorldEdges::Endless => WorldEdges::Walled,
+                };
+            }
+            MainMenuButton::CycleMap => {
+                // the generated world, then every arena in turn
+                settings.arena = match settings.arena {
+                    None if !global_arenas.arenas.is_empty() => Some(0),
+                    Some(index) if index + 1 < global_arenas.arenas.len() => Some(index + 1),
+                    _ => None,
+                };
+            }
+        }
+        let map = map_name(&settings, &global_arenas, &arenas);
+        for (mut text, label) in text_query.iter_mut() {
+            text.sections[0].value = main_menu_label(label.0, &settings, &map);
         }
     }
 }
@@ -1,15 +1,26 @@
+pub mod adaptive;
 pub mod animation;
+pub mod arena;
 pub mod audio;
+pub mod boss;
 pub mod camera;
 pub mod collision;
 pub mod constants;
+pub mod damage;
+pub mod director;
 pub mod enemy;
+pub mod experience;
+pub mod flowfield;
 pub mod gui;
+pub mod passive;
+pub mod pattern;
+pub mod pickup;
 pub mod player;
 pub mod resources;
 pub mod state;
 pub mod weapon;
 pub mod world;
+pub mod worldgen;
 
 pub use constants::*;
 pub use resources::*;
@@ -2,14 +2,24 @@ use audio::GameAudioPlugin;
 use bevy::audio::{AudioPlugin, Volume};
 use bevy::prelude::*;
 
+use adaptive::AdaptiveDirectorPlugin;
 use animation::AnimationPlugin;
+use arena::ArenaPlugin;
+use boss::BossPlugin;
 use bullethell::*;
 use camera::FollowCameraPlugin;
 use collision::CollisionPlugin;
+use damage::DamagePlugin;
+use director::DirectorPlugin;
 use enemy::EnemyPlugin;
+use experience::ExperiencePlugin;
+use flowfield::FlowFieldPlugin;
 use gui::GuiPlugin;
+use passive::PassiveWeaponPlugin;
+use pattern::PatternPlugin;
+use pickup::PickupPlugin;
 use player::PlayerPlugin;
-use state::GameState;
+use state::{GameState, InRun};
 use weapon::WeaponPlugin;
 use world::WorldPlugin;
 
@@ -35,18 +45,29 @@ fn main() {
                 }),
         )
         .init_state::<GameState>()
+        .add_computed_state::<InRun>()
         .insert_resource(ClearColor(Color::srgb(BG_COLOR.0, BG_COLOR.1, BG_COLOR.2)))
         .insert_resource(Msaa::Off)
         .add_plugins(FollowCameraPlugin)
         .add_plugins(GameAudioPlugin)
         .add_plugins(GuiPlugin)
         .add_plugins(WeaponPlugin)
+        .add_plugins(PassiveWeaponPlugin)
         .add_plugins(AnimationPlugin)
         .add_plugins(PlayerPlugin)
         .add_plugins(ResourcesPlugin)
         .add_plugins(WorldPlugin)
+        .add_plugins(ArenaPlugin)
         .add_plugins(EnemyPlugin)
+        .add_plugins(DirectorPlugin)
+        .add_plugins(AdaptiveDirectorPlugin)
+        .add_plugins(FlowFieldPlugin)
+        .add_plugins(BossPlugin)
+        .add_plugins(PatternPlugin)
         .add_plugins(CollisionPlugin)
+        .add_plugins(DamagePlugin)
+        .add_plugins(ExperiencePlugin)
+        .add_plugins(PickupPlugin)
         .add_systems(Update, close_on_esc)
         .run();
 }
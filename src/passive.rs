@@ -0,0 +1,175 @@
+use std::f32::consts::PI;
+
+use bevy::math::vec3;
+use bevy::prelude::*;
+
+use crate::player::Player;
+use crate::state::GameState;
+use crate::world::GameEntity;
+use crate::*;
+
+pub struct PassiveWeaponPlugin;
+
+/// Upgrade levels of the weapons that fire without player input. Level 0 means
+/// the weapon is not owned.
+#[derive(Component, Default)]
+pub struct PassiveWeapons {
+    pub blade_level: u32,
+    pub aura_level: u32,
+}
+
+#[derive(Component)]
+pub struct OrbitingBlade {
+    pub angle: f32,
+    pub damage: f32,
+}
+
+/// Enemies a blade has hit and the elapsed time of each hit, so an enemy
+/// standing in the orbit is only damaged once per `BLADE_HIT_COOLDOWN`.
+#[derive(Component, Default)]
+pub struct BladeHits(pub Vec<(Entity, f32)>);
+
+#[derive(Component)]
+pub struct Aura {
+    pub radius: f32,
+    pub damage: f32,
+    pub timer: Timer,
+}
+
+impl Plugin for PassiveWeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                update_orbiting_blade_count,
+                update_aura,
+                update_orbiting_blade_transform.after(update_orbiting_blade_count),
+                draw_aura.after(update_aura),
+            )
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+impl PassiveWeapons {
+    pub fn blade_count(&self) -> u32 {
+        match self.blade_level {
+            0 => 0,
+            level => (level + 1).min(BLADE_MAX_COUNT),
+        }
+    }
+
+    pub fn blade_damage(&self) -> f32 {
+        BLADE_DAMAGE * level_multiplier(self.blade_level, 0.25)
+    }
+
+    pub fn aura_radius(&self) -> f32 {
+        AURA_RADIUS * level_multiplier(self.aura_level, 0.15)
+    }
+
+    pub fn aura_damage(&self) -> f32 {
+        AURA_DAMAGE * level_multiplier(self.aura_level, 0.3)
+    }
+}
+
+fn level_multiplier(level: u32, per_level: f32) -> f32 {
+    1.0 + per_level * level.saturating_sub(1) as f32
+}
+
+fn update_orbiting_blade_count(
+    mut commands: Commands,
+    handle: Res<GlobalTextureAtlas>,
+    player_query: Query<&PassiveWeapons, (With<Player>, Changed<PassiveWeapons>)>,
+    blade_query: Query<Entity, With<OrbitingBlade>>,
+) {
+    if player_query.is_empty() {
+        return;
+    }
+
+    let passive_weapons = player_query.single();
+    for entity in blade_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let count = passive_weapons.blade_count();
+    for i in 0..count {
+        commands.spawn((
+            SpriteBundle {
+                texture: handle.image.clone().unwrap(),
+                transform: Transform::from_scale(Vec3::splat(SPRITE_SCALE_FACTOR * 1.5)),
+                ..default()
+            },
+            TextureAtlas {
+                layout: handle.layout.clone().unwrap(),
+                index: 15,
+            },
+            OrbitingBlade {
+                angle: i as f32 * 2.0 * PI / count as f32,
+                damage: passive_weapons.blade_damage(),
+            },
+            BladeHits::default(),
+            GameEntity,
+        ));
+    }
+}
+
+fn update_orbiting_blade_transform(
+    time: Res<Time>,
+    player_query: Query<&Transform, With<Player>>,
+    mut blade_query: Query<(&mut Transform, &mut OrbitingBlade), Without<Player>>,
+) {
+    if player_query.is_empty() || blade_query.is_empty() {
+        return;
+    }
+
+    let player_pos = player_query.single().translation;
+    for (mut transform, mut blade) in blade_query.iter_mut() {
+        blade.angle = (blade.angle + BLADE_ANGULAR_SPEED * time.delta_seconds()) % (2.0 * PI);
+        let offset = vec3(blade.angle.cos(), blade.angle.sin(), 0.0) * BLADE_ORBIT_RADIUS;
+        transform.translation = vec3(player_pos.x, player_pos.y, 12.0) + offset;
+        transform.rotation = Quat::from_rotation_z(blade.angle);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_aura(
+    mut commands: Commands,
+    mut player_query: Query<
+        (Entity, &PassiveWeapons, Option<&mut Aura>),
+        (With<Player>, Changed<PassiveWeapons>),
+    >,
+) {
+    if player_query.is_empty() {
+        return;
+    }
+
+    let (entity, passive_weapons, aura) = player_query.single_mut();
+    if passive_weapons.aura_level == 0 {
+        commands.entity(entity).remove::<Aura>();
+        return;
+    }
+
+    match aura {
+        Some(mut aura) => {
+            aura.radius = passive_weapons.aura_radius();
+            aura.damage = passive_weapons.aura_damage();
+        }
+        None => {
+            commands.entity(entity).insert(Aura {
+                radius: passive_weapons.aura_radius(),
+                damage: passive_weapons.aura_damage(),
+                timer: Timer::from_seconds(AURA_TICK_INTERVAL, TimerMode::Repeating),
+            });
+        }
+    }
+}
+
+fn draw_aura(mut gizmos: Gizmos, aura_query: Query<(&Transform, &Aura)>) {
+    for (transform, aura) in aura_query.iter() {
+        gizmos.circle_2d(
+            transform.translation.truncate(),
+            aura.radius,
+            Color::srgba(0.9, 0.8, 0.2, 0.6),
+        );
+    }
+}
@@ -0,0 +1,447 @@
+use std::f32::consts::PI;
+use std::ops::Range;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::enemy::{spawn_enemy_projectile, EnemyProjectile};
+use crate::player::Player;
+use crate::state::GameState;
+use crate::world::wrapped_delta;
+use crate::{GlobalTextureAtlas, Settings, PATTERN_MIN_LOOP_SECS};
+
+pub struct PatternPlugin;
+
+/// A scripted sequence of enemy shots, loaded from a `.pattern.ron` file.
+#[derive(Asset, TypePath, Debug, Clone, Deserialize)]
+pub struct BulletPattern {
+    pub projectile: EnemyProjectile,
+    pub steps: Vec<PatternStep>,
+}
+
+/// Angles are in radians. Aimed steps are relative to the direction of the
+/// player, the rest are relative to the +x axis.
+#[derive(Debug, Clone, Deserialize)]
+pub enum PatternStep {
+    /// `count` shots spread evenly around a full circle.
+    Ring {
+        count: u32,
+        #[serde(default)]
+        offset: f32,
+        /// Random rotation of the whole ring, up to this many radians either
+        /// way.
+        #[serde(default)]
+        jitter: f32,
+    },
+    /// `arms` streams rotating by `turn` after each of `shots` volleys.
+    Spiral {
+        arms: u32,
+        shots: u32,
+        turn: f32,
+        interval: f32,
+    },
+    /// `count` shots spread evenly across `spread`, centred on the player.
+    Fan {
+        count: u32,
+        spread: f32,
+    },
+    /// An aimed stream that sweeps back and forth across `spread`.
+    Wave {
+        shots: u32,
+        spread: f32,
+        frequency: f32,
+        interval: f32,
+    },
+    /// Scales the speed of every following shot.
+    Speed(f32),
+    Delay(f32),
+    Repeat {
+        times: u32,
+        steps: Vec<PatternStep>,
+    },
+}
+
+/// One shot of a flattened pattern, `time` seconds after playback starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatternShot {
+    pub time: f32,
+    pub angle: f32,
+    pub aimed: bool,
+    pub speed_scale: f32,
+}
+
+/// Plays a pattern from the entity's position. The same seed always produces
+/// the same shots.
+#[derive(Component)]
+pub struct PatternPlayer {
+    pub pattern: Handle<BulletPattern>,
+    pub seed: u64,
+    pub looping: bool,
+    elapsed: f32,
+    next_shot: usize,
+    shots: Option<Vec<PatternShot>>,
+    duration: f32,
+}
+
+#[derive(Default)]
+pub struct BulletPatternLoader;
+
+#[derive(Debug, Error)]
+pub enum BulletPatternLoaderError {
+    #[error("could not read bullet pattern: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse bullet pattern: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl Plugin for PatternPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<BulletPattern>()
+            .init_asset_loader::<BulletPatternLoader>()
+            .add_systems(
+                Update,
+                play_bullet_patterns.run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+impl AssetLoader for BulletPatternLoader {
+    type Asset = BulletPattern;
+    type Settings = ();
+    type Error = BulletPatternLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<BulletPattern, BulletPatternLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pattern.ron"]
+    }
+}
+
+impl BulletPattern {
+    /// Expands the steps into shots sorted by time, along with the length of
+    /// the whole pattern including any trailing delay.
+    pub fn flatten(&self, seed: u64) -> (Vec<PatternShot>, f32) {
+        let mut flattener = Flattener {
+            rng: StdRng::seed_from_u64(seed),
+            time: 0.0,
+            speed_scale: 1.0,
+            shots: Vec::new(),
+        };
+        flattener.steps(&self.steps);
+        (flattener.shots, flattener.time)
+    }
+}
+
+struct Flattener {
+    rng: StdRng,
+    time: f32,
+    speed_scale: f32,
+    shots: Vec<PatternShot>,
+}
+
+impl Flattener {
+    fn steps(&mut self, steps: &[PatternStep]) {
+        for step in steps {
+            self.step(step);
+        }
+    }
+
+    fn step(&mut self, step: &PatternStep) {
+        match step {
+            PatternStep::Ring {
+                count,
+                offset,
+                jitter,
+            } => {
+                let jitter = if *jitter > 0.0 {
+                    self.rng.gen_range(-jitter..=*jitter)
+                } else {
+                    0.0
+                };
+                for i in 0..*count {
+                    let angle = offset + jitter + i as f32 * 2.0 * PI / *count as f32;
+                    self.shot(angle, false);
+                }
+            }
+            PatternStep::Spiral {
+                arms,
+                shots,
+                turn,
+                interval,
+            } => {
+                for shot in 0..*shots {
+                    for arm in 0..*arms {
+                        let angle = shot as f32 * turn + arm as f32 * 2.0 * PI / *arms as f32;
+                        self.shot(angle, false);
+                    }
+                    self.time += interval;
+                }
+            }
+            PatternStep::Fan { count, spread } => {
+                for i in 0..*count {
+                    let t = if *count > 1 {
+                        i as f32 / (*count - 1) as f32 - 0.5
+                    } else {
+                        0.0
+                    };
+                    self.shot(t * spread, true);
+                }
+            }
+            PatternStep::Wave {
+                shots,
+                spread,
+                frequency,
+                interval,
+            } => {
+                for shot in 0..*shots {
+                    let angle = (shot as f32 * frequency).sin() * spread / 2.0;
+                    self.shot(angle, true);
+                    self.time += interval;
+                }
+            }
+            PatternStep::Speed(scale) => self.speed_scale = *scale,
+            PatternStep::Delay(secs) => self.time += secs,
+            PatternStep::Repeat { times, steps } => {
+                for _ in 0..*times {
+                    self.steps(steps);
+                }
+            }
+        }
+    }
+
+    fn shot(&mut self, angle: f32, aimed: bool) {
+        self.shots.push(PatternShot {
+            time: self.time,
+            angle,
+            aimed,
+            speed_scale: self.speed_scale,
+        });
+    }
+}
+
+impl PatternPlayer {
+    pub fn new(pattern: Handle<BulletPattern>, seed: u64, looping: bool) -> Self {
+        Self {
+            pattern,
+            seed,
+            looping,
+            elapsed: 0.0,
+            next_shot: 0,
+            shots: None,
+            duration: 0.0,
+        }
+    }
+
+    /// Moves playback on by `delta` seconds and returns the range of shots
+    /// now due. A looping pattern starts over once its last shot and any
+    /// trailing delay are done, but never sooner than `PATTERN_MIN_LOOP_SECS`
+    /// so one without delays can't fire a volley every frame.
+    fn advance(&mut self, delta: f32) -> Range<usize> {
+        let shots = self.shots.as_deref().unwrap_or_default();
+        self.elapsed += delta;
+        let period = self.duration.max(PATTERN_MIN_LOOP_SECS);
+        if self.looping && self.next_shot >= shots.len() && self.elapsed >= period {
+            // keep the overshoot so the loop doesn't drift
+            self.elapsed -= period;
+            self.next_shot = 0;
+        }
+
+        let start = self.next_shot;
+        while shots
+            .get(self.next_shot)
+            .is_some_and(|shot| shot.time <= self.elapsed)
+        {
+            self.next_shot += 1;
+        }
+        start..self.next_shot
+    }
+}
+
+fn play_bullet_patterns(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    handle: Res<GlobalTextureAtlas>,
+    patterns: Res<Assets<BulletPattern>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut pattern_query: Query<(Entity, &Transform, &mut PatternPlayer), Without<Player>>,
+) {
+    if player_query.is_empty() || pattern_query.is_empty() {
+        return;
+    }
+
+    let player_pos = player_query.single().translation.truncate();
+    for (entity, transform, mut player) in pattern_query.iter_mut() {
+        let Some(pattern) = patterns.get(&player.pattern) else {
+            continue;
+        };
+        if player.shots.is_none() {
+            let (shots, duration) = pattern.flatten(player.seed);
+            player.shots = Some(shots);
+            player.duration = duration;
+        }
+
+        let pos = transform.translation.truncate();
+        let aim = wrapped_delta(pos, player_pos, settings.wraps_world()).to_angle();
+        let due = player.advance(time.delta_seconds());
+        let shots = player.shots.as_deref().unwrap_or_default();
+        for shot in &shots[due] {
+            let angle = if shot.aimed {
+                aim + shot.angle
+            } else {
+                shot.angle
+            };
+            let projectile = EnemyProjectile {
+                speed: pattern.projectile.speed * shot.speed_scale,
+                ..pattern.projectile
+            };
+            spawn_enemy_projectile(
+                &mut commands,
+                &handle,
+                entity,
+                pos,
+                Vec2::from_angle(angle),
+                &projectile,
+            );
+        }
+
+        if !player.looping && player.next_shot >= shots.len() {
+            commands.entity(entity).remove::<PatternPlayer>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(steps: Vec<PatternStep>) -> BulletPattern {
+        BulletPattern {
+            projectile: EnemyProjectile {
+                damage: 1.0,
+                speed: 1.0,
+                lifetime: 1.0,
+                sprite_index: 0,
+            },
+            steps,
+        }
+    }
+
+    fn jittered_ring() -> BulletPattern {
+        pattern(vec![PatternStep::Ring {
+            count: 8,
+            offset: 0.0,
+            jitter: 0.5,
+        }])
+    }
+
+    #[test]
+    fn same_seed_gives_same_shots() {
+        let pattern = jittered_ring();
+        assert_eq!(pattern.flatten(7), pattern.flatten(7));
+    }
+
+    #[test]
+    fn different_seeds_change_random_steps() {
+        let pattern = jittered_ring();
+        assert_ne!(pattern.flatten(1).0, pattern.flatten(2).0);
+    }
+
+    #[test]
+    fn ring_spreads_shots_around_a_circle() {
+        let (shots, _) = pattern(vec![PatternStep::Ring {
+            count: 4,
+            offset: 0.1,
+            jitter: 0.0,
+        }])
+        .flatten(0);
+
+        assert_eq!(shots.len(), 4);
+        for (i, shot) in shots.iter().enumerate() {
+            assert!((shot.angle - (0.1 + i as f32 * PI / 2.0)).abs() < 1e-5);
+            assert!(!shot.aimed);
+        }
+    }
+
+    #[test]
+    fn fan_is_centred_on_the_player() {
+        let (shots, _) = pattern(vec![PatternStep::Fan {
+            count: 3,
+            spread: 1.0,
+        }])
+        .flatten(0);
+
+        let angles: Vec<_> = shots.iter().map(|shot| shot.angle).collect();
+        assert_eq!(angles, vec![-0.5, 0.0, 0.5]);
+        assert!(shots.iter().all(|shot| shot.aimed));
+    }
+
+    #[test]
+    fn repeat_and_delay_move_shots_forward() {
+        let (shots, duration) = pattern(vec![PatternStep::Repeat {
+            times: 3,
+            steps: vec![
+                PatternStep::Fan {
+                    count: 2,
+                    spread: 0.4,
+                },
+                PatternStep::Delay(0.5),
+            ],
+        }])
+        .flatten(0);
+
+        assert_eq!(shots.len(), 6);
+        let times: Vec<_> = shots.iter().map(|shot| shot.time).collect();
+        assert_eq!(times, vec![0.0, 0.0, 0.5, 0.5, 1.0, 1.0]);
+        assert_eq!(duration, 1.5);
+    }
+
+    /// Shots fired by a looping player over `secs` of 60 fps frames.
+    fn play(pattern: &BulletPattern, secs: f32) -> usize {
+        let mut player = PatternPlayer::new(Handle::default(), 0, true);
+        let (shots, duration) = pattern.flatten(0);
+        player.shots = Some(shots);
+        player.duration = duration;
+
+        let frames = (secs * 60.0).round() as usize;
+        (0..frames).map(|_| player.advance(1.0 / 60.0).len()).sum()
+    }
+
+    #[test]
+    fn looping_waits_for_trailing_delay() {
+        let pattern = pattern(vec![
+            PatternStep::Fan {
+                count: 2,
+                spread: 0.4,
+            },
+            PatternStep::Delay(0.5),
+        ]);
+        // a volley at 0.0, 0.5, 1.0 and 1.5
+        assert_eq!(play(&pattern, 1.9), 8);
+    }
+
+    #[test]
+    fn looping_without_delay_is_rate_limited() {
+        let pattern = pattern(vec![PatternStep::Ring {
+            count: 8,
+            offset: 0.0,
+            jitter: 0.0,
+        }]);
+        let volleys = (1.0 / PATTERN_MIN_LOOP_SECS) as usize;
+        assert_eq!(play(&pattern, 0.99), 8 * volleys);
+    }
+}
@@ -0,0 +1,346 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use kd_tree::KdTree;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::adaptive::AdaptiveDirector;
+use crate::collision::{Collidable, EnemyKdTree};
+use crate::damage::{DamageEvent, DamageKind, Health, Invulnerable};
+use crate::enemy::{Enemy, EnemyDiedEvent};
+use crate::experience::{Magnetized, XpGem};
+use crate::player::Player;
+use crate::state::GameState;
+use crate::weapon::{Bullet, Faction};
+use crate::world::{wrapped_copies, wrapped_delta, GameEntity};
+use crate::*;
+
+pub struct PickupPlugin;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickupKind {
+    HealthPotion,
+    /// Pulls in every experience gem while active.
+    Magnet,
+    /// Hits every enemy around the player and clears their shots.
+    Bomb,
+    RapidFire,
+    Invulnerability,
+}
+
+/// Dropped by dead enemies, despawns if left lying around for too long.
+#[derive(Component)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    lifetime: Timer,
+}
+
+/// A timed effect from a collected pickup.
+pub struct PowerUp {
+    pub kind: PickupKind,
+    pub timer: Timer,
+}
+
+#[derive(Resource, Default)]
+pub struct PowerUps(pub Vec<PowerUp>);
+
+#[derive(Resource)]
+pub struct PickupKdTree(pub KdTree<Collidable>);
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PowerUps>()
+            .insert_resource(PickupKdTree::default())
+            .add_systems(OnEnter(GameState::GameInit), reset_power_ups)
+            .add_systems(
+                Update,
+                (
+                    (
+                        spawn_pickups,
+                        collect_pickups,
+                        tick_power_ups,
+                        magnetize_gems,
+                        despawn_expired_pickups,
+                    )
+                        .chain(),
+                    update_pickup_kd_tree.run_if(
+                        on_timer(Duration::from_secs_f32(KD_TREE_REFRESH_RATE))
+                            .or_else(pickups_changed),
+                    ),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+impl PickupKind {
+    pub const ALL: [PickupKind; 5] = [
+        PickupKind::HealthPotion,
+        PickupKind::Magnet,
+        PickupKind::Bomb,
+        PickupKind::RapidFire,
+        PickupKind::Invulnerability,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PickupKind::HealthPotion => "Health",
+            PickupKind::Magnet => "Magnet",
+            PickupKind::Bomb => "Bomb",
+            PickupKind::RapidFire => "Rapid Fire",
+            PickupKind::Invulnerability => "Invulnerable",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            PickupKind::HealthPotion => Color::srgb(0.9, 0.2, 0.2),
+            PickupKind::Magnet => Color::srgb(0.3, 0.5, 1.0),
+            PickupKind::Bomb => Color::srgb(0.15, 0.15, 0.15),
+            PickupKind::RapidFire => Color::srgb(1.0, 0.7, 0.1),
+            PickupKind::Invulnerability => Color::srgb(1.0, 1.0, 0.6),
+        }
+    }
+
+    /// Relative chance of being picked from the drop table.
+    fn weight(self) -> u32 {
+        match self {
+            PickupKind::HealthPotion => 40,
+            PickupKind::Magnet => 15,
+            PickupKind::Bomb => 10,
+            PickupKind::RapidFire => 20,
+            PickupKind::Invulnerability => 15,
+        }
+    }
+
+    /// Seconds the effect lasts, `None` for the ones that apply at once.
+    fn duration(self) -> Option<f32> {
+        match self {
+            PickupKind::HealthPotion | PickupKind::Bomb => None,
+            PickupKind::Magnet => Some(POWERUP_MAGNET_SECS),
+            PickupKind::RapidFire => Some(POWERUP_RAPID_FIRE_SECS),
+            PickupKind::Invulnerability => Some(POWERUP_INVULNERABILITY_SECS),
+        }
+    }
+}
+
+impl PowerUps {
+    pub fn is_active(&self, kind: PickupKind) -> bool {
+        self.0.iter().any(|power_up| power_up.kind == kind)
+    }
+
+    /// Multiplies the fire rate of every weapon.
+    pub fn fire_rate_scale(&self) -> f32 {
+        if self.is_active(PickupKind::RapidFire) {
+            return POWERUP_RAPID_FIRE_SCALE;
+        }
+
+        1.0
+    }
+
+    /// Starts the effect, or restarts its timer when it's already running.
+    fn activate(&mut self, kind: PickupKind, secs: f32) {
+        let timer = Timer::from_seconds(secs, TimerMode::Once);
+        match self.0.iter_mut().find(|power_up| power_up.kind == kind) {
+            Some(power_up) => power_up.timer = timer,
+            None => self.0.push(PowerUp { kind, timer }),
+        }
+    }
+}
+
+impl Default for PickupKdTree {
+    fn default() -> Self {
+        Self(KdTree::build_by_ordered_float(vec![]))
+    }
+}
+
+fn reset_power_ups(mut power_ups: ResMut<PowerUps>) {
+    power_ups.0.clear();
+}
+
+fn spawn_pickups(
+    mut commands: Commands,
+    adaptive: Res<AdaptiveDirector>,
+    mut events: EventReader<EnemyDiedEvent>,
+) {
+    let mut rng = rand::thread_rng();
+    for event in events.read() {
+        let chance = event.kind.stats().drop_chance * adaptive.drop_scale;
+        if rng.gen::<f32>() >= chance {
+            continue;
+        }
+        let Ok(kind) = PickupKind::ALL.choose_weighted(&mut rng, |kind| kind.weight()) else {
+            continue;
+        };
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: kind.color(),
+                    custom_size: Some(Vec2::splat(PICKUP_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(event.pos.extend(1.0)),
+                ..default()
+            },
+            Pickup {
+                kind: *kind,
+                lifetime: Timer::from_seconds(PICKUP_LIFETIME_SECS, TimerMode::Once),
+            },
+            GameEntity,
+        ));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn collect_pickups(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    tree: Res<PickupKdTree>,
+    enemy_tree: Res<EnemyKdTree>,
+    mut power_ups: ResMut<PowerUps>,
+    mut player_query: Query<(Entity, &Transform, &mut Health), With<Player>>,
+    pickup_query: Query<&Pickup>,
+    enemy_query: Query<&Health, (With<Enemy>, Without<Player>)>,
+    bullet_query: Query<(Entity, &Transform, &Faction), With<Bullet>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    if player_query.is_empty() {
+        return;
+    }
+
+    let (player, transform, mut health) = player_query.single_mut();
+    let player_pos = transform.translation.truncate();
+    let reach = PICKUP_COLLECT_RADIUS + PICKUP_SIZE / 2.0;
+    for p in tree.0.within_radius(&[player_pos.x, player_pos.y], reach) {
+        // the tree is refreshed on a timer and can still list collected pickups
+        let Ok(pickup) = pickup_query.get(p.entity) else {
+            continue;
+        };
+        commands.entity(p.entity).despawn();
+
+        match pickup.kind {
+            PickupKind::HealthPotion => {
+                health.0 = (health.0 + PICKUP_HEAL_AMOUNT).min(PLAYER_HEALTH);
+            }
+            PickupKind::Bomb => {
+                for e in enemy_tree.within_radius(player_pos, PICKUP_BOMB_RADIUS) {
+                    if !enemy_query.get(e.entity).is_ok_and(|health| health.0 > 0.0) {
+                        continue;
+                    }
+                    damage_events.send(DamageEvent {
+                        target: e.entity,
+                        source: player,
+                        amount: PICKUP_BOMB_DAMAGE,
+                        kind: DamageKind::Explosive,
+                    });
+                }
+                for (bullet, bullet_transform, faction) in bullet_query.iter() {
+                    let pos = bullet_transform.translation.truncate();
+                    if *faction == Faction::Enemy
+                        && wrapped_delta(pos, player_pos, settings.wraps_world()).length()
+                            <= PICKUP_BOMB_RADIUS
+                    {
+                        commands.entity(bullet).despawn();
+                    }
+                }
+            }
+            // the power up's timer decides when it ends, see `tick_power_ups`
+            PickupKind::Invulnerability => {
+                commands
+                    .entity(player)
+                    .insert(Invulnerable::until_removed());
+            }
+            PickupKind::Magnet | PickupKind::RapidFire => {}
+        }
+        if let Some(secs) = pickup.kind.duration() {
+            power_ups.activate(pickup.kind, secs);
+        }
+    }
+}
+
+fn tick_power_ups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut power_ups: ResMut<PowerUps>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    if power_ups.0.is_empty() {
+        return;
+    }
+
+    power_ups.0.retain_mut(|power_up| {
+        if !power_up.timer.tick(time.delta()).finished() {
+            return true;
+        }
+        if power_up.kind == PickupKind::Invulnerability {
+            for player in player_query.iter() {
+                commands.entity(player).remove::<Invulnerable>();
+            }
+        }
+        false
+    });
+}
+
+fn magnetize_gems(
+    mut commands: Commands,
+    power_ups: Res<PowerUps>,
+    gem_query: Query<Entity, (With<XpGem>, Without<Magnetized>)>,
+) {
+    if !power_ups.is_active(PickupKind::Magnet) {
+        return;
+    }
+
+    for entity in gem_query.iter() {
+        commands.entity(entity).insert(Magnetized);
+    }
+}
+
+fn despawn_expired_pickups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pickup_query: Query<(Entity, &mut Pickup)>,
+) {
+    for (entity, mut pickup) in pickup_query.iter_mut() {
+        if pickup.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Rebuilds the tree early when pickups drop or disappear, so fresh drops can
+/// be collected right away.
+fn pickups_changed(
+    added_query: Query<(), Added<Pickup>>,
+    mut removed: RemovedComponents<Pickup>,
+) -> bool {
+    // read every removal so they don't trigger another rebuild next frame
+    removed.read().count() > 0 || !added_query.is_empty()
+}
+
+fn update_pickup_kd_tree(
+    settings: Res<Settings>,
+    mut tree: ResMut<PickupKdTree>,
+    pickup_query: Query<(&Transform, Entity), With<Pickup>>,
+) {
+    let mut pickups = Vec::new();
+    for (transform, entity) in pickup_query.iter() {
+        let pos = transform.translation.truncate();
+        let copies = if settings.wraps_world() {
+            wrapped_copies(pos, PICKUP_COLLECT_RADIUS + PICKUP_SIZE)
+        } else {
+            Vec::new()
+        };
+        for pos in std::iter::once(pos).chain(copies) {
+            pickups.push(Collidable {
+                entity,
+                pos,
+                radius: PICKUP_SIZE / 2.0,
+            });
+        }
+    }
+
+    tree.0 = KdTree::build_by_ordered_float(pickups);
+}
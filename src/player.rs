@@ -1,13 +1,23 @@
 use bevy::{math::vec3, prelude::*};
 
-use crate::{state::GameState, ENEMY_DAMAGE, PLAYER_SPEED};
+use crate::damage::{DamageDealtEvent, Health, Knockback};
+use crate::world::wrapped_delta;
+use crate::{
+    state::GameState, Settings, PLAYER_KNOCKBACK_SECS, PLAYER_KNOCKBACK_SPEED, PLAYER_SPEED,
+};
 
 pub struct PlayerPlugin;
 
 #[derive(Component)]
 pub struct Player;
+
+/// Bonuses picked on level up, as multipliers of the base stats.
 #[derive(Component)]
-pub struct Health(pub f32);
+pub struct PlayerUpgrades {
+    pub damage: f32,
+    pub fire_rate: f32,
+    pub move_speed: f32,
+}
 
 #[derive(Component, Default)]
 pub enum PlayerState {
@@ -16,34 +26,60 @@ pub enum PlayerState {
     Moving,
 }
 
-#[derive(Event)]
-pub struct PlayerEnemyCollisionEvent;
-
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<PlayerEnemyCollisionEvent>().add_systems(
+        app.add_systems(
             Update,
             (
                 handle_player_death,
                 handle_player_input,
-                handle_player_enemy_collision_events,
+                knock_back_player_on_hit,
             )
                 .run_if(in_state(GameState::InGame)),
         );
     }
 }
 
-fn handle_player_enemy_collision_events(
-    mut player_query: Query<&mut Health, With<Player>>,
-    mut events: EventReader<PlayerEnemyCollisionEvent>,
+impl Default for PlayerUpgrades {
+    fn default() -> Self {
+        Self {
+            damage: 1.0,
+            fire_rate: 1.0,
+            move_speed: 1.0,
+        }
+    }
+}
+
+fn knock_back_player_on_hit(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    player_query: Query<(Entity, &Transform), With<Player>>,
+    source_query: Query<&Transform, Without<Player>>,
+    mut events: EventReader<DamageDealtEvent>,
 ) {
     if player_query.is_empty() {
         return;
     }
 
-    let mut health = player_query.single_mut();
-    for _ in events.read() {
-        health.0 -= ENEMY_DAMAGE;
+    let (player, player_transform) = player_query.single();
+    for event in events.read() {
+        if event.target != player {
+            continue;
+        }
+        let Ok(source_transform) = source_query.get(event.source) else {
+            continue;
+        };
+
+        let away = wrapped_delta(
+            source_transform.translation.truncate(),
+            player_transform.translation.truncate(),
+            settings.wraps_world(),
+        )
+        .normalize_or_zero();
+        commands.entity(player).insert(Knockback {
+            velocity: away * PLAYER_KNOCKBACK_SPEED,
+            timer: Timer::from_seconds(PLAYER_KNOCKBACK_SECS, TimerMode::Once),
+        });
     }
 }
 
@@ -62,14 +98,14 @@ fn handle_player_death(
 }
 
 fn handle_player_input(
-    mut player_query: Query<(&mut Transform, &mut PlayerState), With<Player>>,
+    mut player_query: Query<(&mut Transform, &mut PlayerState, &PlayerUpgrades), With<Player>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
 ) {
     if player_query.is_empty() {
         return;
     }
 
-    let (mut transform, mut player_state) = player_query.single_mut();
+    let (mut transform, mut player_state, upgrades) = player_query.single_mut();
     let w_key = keyboard_input.pressed(KeyCode::KeyW) || keyboard_input.pressed(KeyCode::ArrowUp);
     let a_key = keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::ArrowLeft);
     let s_key = keyboard_input.pressed(KeyCode::KeyS) || keyboard_input.pressed(KeyCode::ArrowDown);
@@ -94,7 +130,7 @@ fn handle_player_input(
     delta = delta.normalize_or_zero();
 
     if w_key || a_key || s_key || d_key {
-        transform.translation += vec3(delta.x, delta.y, 0.0) * PLAYER_SPEED;
+        transform.translation += vec3(delta.x, delta.y, 0.0) * PLAYER_SPEED * upgrades.move_speed;
         transform.translation.z = 10.0;
         *player_state = PlayerState::Moving;
     } else {
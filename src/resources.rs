@@ -1,8 +1,12 @@
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 
+use crate::arena::Arena;
 use crate::constants::*;
+use crate::director::WaveSchedule;
+use crate::pattern::BulletPattern;
 use crate::state::GameState;
+use crate::weapon::WeaponDef;
 
 pub struct ResourcesPlugin;
 
@@ -13,8 +17,83 @@ pub struct GlobalTextureAtlas {
 }
 
 #[derive(Resource, Default)]
-pub struct GlobalAudioSource{
-    pub weapon_effect: Option<Handle<AudioSource>>
+pub struct GlobalAudioSource {
+    pub weapon_effect: Option<Handle<AudioSource>>,
+    pub reload_effect: Option<Handle<AudioSource>>,
+}
+
+#[derive(Resource, Default)]
+pub struct GlobalWeaponDefs {
+    pub starting_weapons: Vec<Handle<WeaponDef>>,
+    pub unlockable_weapons: Vec<Handle<WeaponDef>>,
+}
+
+#[derive(Resource, Default)]
+pub struct GlobalBulletPatterns {
+    pub boss_volley: Handle<BulletPattern>,
+    pub boss_volley_enraged: Handle<BulletPattern>,
+}
+
+#[derive(Resource, Default)]
+pub struct GlobalWaveSchedule {
+    pub schedule: Handle<WaveSchedule>,
+}
+
+#[derive(Resource, Default)]
+pub struct GlobalArenas {
+    pub arenas: Vec<Handle<Arena>>,
+}
+
+/// Player options chosen from the main menu.
+#[derive(Resource, Default)]
+pub struct Settings {
+    pub adaptive_director: bool,
+    pub edges: WorldEdges,
+    /// Seed for the generated map, a random one is rolled every run when unset.
+    pub world_seed: Option<u64>,
+    /// Index into `GlobalArenas` of the hand-authored map to play, the
+    /// generated world when unset.
+    pub arena: Option<usize>,
+}
+
+/// What happens at the edge of the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorldEdges {
+    /// A wall stops everything at the edge.
+    #[default]
+    Walled,
+    /// Leaving one edge enters from the opposite one.
+    Wrap,
+    /// No edge at all, the generated map goes on in every direction.
+    Endless,
+}
+
+impl Settings {
+    /// Hand-authored arenas have a fixed size, they get walls instead of an
+    /// endless map.
+    pub fn world_edges(&self) -> WorldEdges {
+        if self.edges == WorldEdges::Endless && self.arena.is_some() {
+            return WorldEdges::Walled;
+        }
+
+        self.edges
+    }
+
+    pub fn wraps_world(&self) -> bool {
+        self.world_edges() == WorldEdges::Wrap
+    }
+}
+
+impl GlobalArenas {
+    /// The arena picked in `Settings`, if it's loaded.
+    pub fn selected<'a>(
+        &self,
+        settings: &Settings,
+        arenas: &'a Assets<Arena>,
+    ) -> Option<&'a Arena> {
+        let handle = self.arenas.get(settings.arena?)?;
+        arenas.get(handle)
+    }
 }
 
 #[derive(Resource, Debug)]
@@ -24,6 +103,16 @@ impl Plugin for ResourcesPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(GlobalTextureAtlas::default())
             .insert_resource(GlobalAudioSource::default())
+            .insert_resource(GlobalWeaponDefs::default())
+            .insert_resource(GlobalBulletPatterns::default())
+            .insert_resource(GlobalWaveSchedule::default())
+            .insert_resource(GlobalArenas::default())
+            .insert_resource(Settings {
+                world_seed: std::env::var(WORLD_SEED_ENV)
+                    .ok()
+                    .and_then(|seed| seed.parse().ok()),
+                ..default()
+            })
             .insert_resource(CursorPosition(None))
             .add_systems(OnEnter(GameState::Loading), load_assets)
             .add_systems(
@@ -33,9 +122,14 @@ impl Plugin for ResourcesPlugin {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn load_assets(
     mut texture_atlas: ResMut<GlobalTextureAtlas>,
     mut audio_source: ResMut<GlobalAudioSource>,
+    mut weapon_defs: ResMut<GlobalWeaponDefs>,
+    mut bullet_patterns: ResMut<GlobalBulletPatterns>,
+    mut wave_schedule: ResMut<GlobalWaveSchedule>,
+    mut arenas: ResMut<GlobalArenas>,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     mut next_state: ResMut<NextState<GameState>>,
@@ -53,6 +147,24 @@ fn load_assets(
     texture_atlas.layout = Some(texture_atlas_layouts.add(layout));
 
     audio_source.weapon_effect = Some(asset_server.load("audio/effects/attack.wav"));
+    audio_source.reload_effect = Some(asset_server.load("audio/effects/reload.wav"));
+
+    weapon_defs.starting_weapons = STARTING_WEAPON_PATHS
+        .iter()
+        .map(|path| asset_server.load(*path))
+        .collect();
+    weapon_defs.unlockable_weapons = UNLOCKABLE_WEAPON_PATHS
+        .iter()
+        .map(|path| asset_server.load(*path))
+        .collect();
+
+    bullet_patterns.boss_volley = asset_server.load(BOSS_VOLLEY_PATTERN_PATH);
+    bullet_patterns.boss_volley_enraged = asset_server.load(BOSS_VOLLEY_ENRAGED_PATTERN_PATH);
+    wave_schedule.schedule = asset_server.load(WAVE_SCHEDULE_PATH);
+    arenas.arenas = ARENA_PATHS
+        .iter()
+        .map(|path| asset_server.load(*path))
+        .collect();
 
     next_state.set(GameState::MainMenu);
 }
@@ -7,4 +7,19 @@ pub enum GameState {
     MainMenu,
     GameInit,
     InGame,
+    /// The run is paused to pick an upgrade.
+    LevelUp,
+}
+
+/// Active for the whole run, so pausing into `LevelUp` and back doesn't
+/// trigger run setup or teardown.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct InRun;
+
+impl ComputedStates for InRun {
+    type SourceStates = GameState;
+
+    fn compute(state: GameState) -> Option<Self> {
+        matches!(state, GameState::InGame | GameState::LevelUp).then_some(InRun)
+    }
 }
@@ -1,61 +1,387 @@
 use std::f32::consts::PI;
-use std::time::Instant;
 
 use audio::SoundEffect;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::input::mouse::MouseWheel;
 use bevy::math::{vec2, vec3};
 use bevy::prelude::*;
 use bevy::time::Stopwatch;
 use rand::Rng;
+use serde::Deserialize;
+use thiserror::Error;
 
-use crate::player::Player;
+use crate::collision::EnemyKdTree;
+use crate::damage::{Critical, DamageKind, Health};
+use crate::enemy::Enemy;
+use crate::pickup::PowerUps;
+use crate::player::{Player, PlayerUpgrades};
 use crate::state::GameState;
-use crate::world::GameEntity;
+use crate::world::{wrapped_delta, GameEntity, Obstacle};
 use crate::*;
 
 pub struct WeaponPlugin;
 
 #[derive(Component)]
-pub struct Weapon;
+pub struct Weapon(pub Handle<WeaponDef>);
+
+#[derive(Component)]
+pub struct ActiveWeapon;
+
+#[derive(Component, Default)]
+pub struct WeaponInventory {
+    pub slots: Vec<Entity>,
+    pub active: usize,
+}
 
 #[derive(Component)]
 pub struct WeaponTimer(pub Stopwatch);
 #[derive(Component)]
-pub struct Bullet;
+pub struct Ammo {
+    pub magazine: u32,
+    pub reserve: u32,
+}
+#[derive(Component, Deref, DerefMut)]
+pub struct Reloading(pub Timer);
 #[derive(Component)]
-pub struct SpawnInstant(pub Instant);
+pub struct Bullet {
+    pub damage: f32,
+    pub kind: DamageKind,
+    /// The weapon or enemy that fired the bullet.
+    pub source: Entity,
+    pub knockback: f32,
+    pub speed: f32,
+    /// Ticked only while the game runs, so pauses don't eat into it.
+    pub lifetime: Timer,
+}
+/// The side a bullet was fired by. Bullets only collide with the other side.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Enemy,
+}
+#[derive(Component)]
+pub struct BulletDirection(pub Vec3);
+#[derive(Component)]
+pub struct Pierce(pub u32);
+#[derive(Component)]
+pub struct Ricochet(pub u32);
+#[derive(Component, Clone, Copy, Debug, Deserialize)]
+pub struct Explosive {
+    pub radius: f32,
+    /// Damage at the centre, falling off linearly to zero at `radius`.
+    pub damage: f32,
+}
+#[derive(Component, Default)]
+pub struct HitEnemies(pub Vec<Entity>);
+/// A continuous beam currently being fired by a weapon.
 #[derive(Component)]
-struct BulletDirection(Vec3);
+pub struct Beam {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub width: f32,
+    pub damage_per_second: f32,
+    pub kind: DamageKind,
+}
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct BeamDef {
+    pub range: f32,
+    /// Distance from the beam line at which enemies are hit.
+    pub width: f32,
+    pub damage_per_second: f32,
+}
+#[derive(Component, Clone, Copy, Debug, Deserialize)]
+pub struct Homing {
+    /// Maximum steering in radians per second.
+    pub turn_rate: f32,
+    #[serde(skip)]
+    pub target: Option<Entity>,
+}
+
+/// Weapon stats loaded from a `.weapon.ron` file under `assets/weapons/`.
+#[derive(Asset, TypePath, Debug)]
+pub struct WeaponDef {
+    /// Seconds between shots.
+    pub fire_interval: f32,
+    /// Total width of the firing cone in radians.
+    pub spread: f32,
+    pub pellets: usize,
+    pub projectile_speed: f32,
+    pub projectile_lifetime: f32,
+    pub damage: f32,
+    pub damage_kind: DamageKind,
+    pub crit_chance: f32,
+    pub crit_multiplier: f32,
+    /// Speed at which a hit enemy is pushed along the bullet direction.
+    pub knockback: f32,
+    pub sprite_index: usize,
+    pub projectile_sprite_index: usize,
+    pub magazine_size: u32,
+    /// Ammo carried on top of the first magazine.
+    pub reserve_ammo: u32,
+    /// Seconds to refill the magazine.
+    pub reload_time: f32,
+    /// Distinct enemies a projectile can hit before it despawns.
+    pub pierce: u32,
+    /// Bounces off the world bounds before the projectile leaves the arena.
+    pub ricochets: u32,
+    pub explosion: Option<Explosive>,
+    pub homing: Option<Homing>,
+    /// Fires a continuous beam instead of projectiles. Ammo drains once per
+    /// `fire_interval` while the beam is held.
+    pub beam: Option<BeamDef>,
+    /// Falls back to the global weapon effect when unset.
+    pub sound: Option<Handle<AudioSource>>,
+}
+
+#[derive(Deserialize)]
+struct WeaponDefFile {
+    fire_interval: f32,
+    spread: f32,
+    pellets: usize,
+    projectile_speed: f32,
+    projectile_lifetime: f32,
+    damage: f32,
+    #[serde(default)]
+    damage_kind: DamageKind,
+    #[serde(default)]
+    crit_chance: f32,
+    #[serde(default = "default_crit_multiplier")]
+    crit_multiplier: f32,
+    #[serde(default)]
+    knockback: f32,
+    sprite_index: usize,
+    projectile_sprite_index: usize,
+    magazine_size: u32,
+    reserve_ammo: u32,
+    reload_time: f32,
+    #[serde(default = "default_pierce")]
+    pierce: u32,
+    #[serde(default)]
+    ricochets: u32,
+    #[serde(default)]
+    explosion: Option<Explosive>,
+    #[serde(default)]
+    homing: Option<Homing>,
+    #[serde(default)]
+    beam: Option<BeamDef>,
+    #[serde(default)]
+    sound: Option<String>,
+}
+
+#[derive(Default)]
+pub struct WeaponDefLoader;
+
+#[derive(Debug, Error)]
+pub enum WeaponDefLoaderError {
+    #[error("could not read weapon definition: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse weapon definition: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("{field} must be a positive number of seconds, got {value}")]
+    InvalidInterval { field: &'static str, value: f32 },
+    #[error("{field} can't be a negative number of seconds, got {value}")]
+    InvalidDuration { field: &'static str, value: f32 },
+}
 
 impl Plugin for WeaponPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                update_weapon_transform,
-                update_bullets,
-                handle_weapon_input,
-                despawn_old_bullets,
-            )
-                .run_if(in_state(GameState::InGame)),
-        );
+        app.init_asset::<WeaponDef>()
+            .init_asset_loader::<WeaponDefLoader>()
+            .add_systems(
+                Update,
+                (
+                    update_weapon_sprite,
+                    update_weapon_transform,
+                    update_homing_bullets.before(update_bullets),
+                    update_bullets,
+                    tick_weapon_timers,
+                    handle_weapon_switch_input,
+                    init_weapon_stats,
+                    handle_reload_input,
+                    update_reloads,
+                    handle_weapon_input
+                        .after(tick_weapon_timers)
+                        .after(update_reloads),
+                    update_weapon_beams.after(update_weapon_transform),
+                    draw_weapon_beams.after(update_weapon_beams),
+                    despawn_old_bullets,
+                )
+                    .run_if(in_state(GameState::InGame)),
+            );
     }
 }
 
+impl AssetLoader for WeaponDefLoader {
+    type Asset = WeaponDef;
+    type Settings = ();
+    type Error = WeaponDefLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<WeaponDef, WeaponDefLoaderError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let file = WeaponDefFile::parse(&bytes)?;
+
+        Ok(WeaponDef {
+            fire_interval: file.fire_interval,
+            spread: file.spread,
+            pellets: file.pellets,
+            projectile_speed: file.projectile_speed,
+            projectile_lifetime: file.projectile_lifetime,
+            damage: file.damage,
+            damage_kind: file.damage_kind,
+            crit_chance: file.crit_chance,
+            crit_multiplier: file.crit_multiplier,
+            knockback: file.knockback,
+            sprite_index: file.sprite_index,
+            projectile_sprite_index: file.projectile_sprite_index,
+            magazine_size: file.magazine_size,
+            reserve_ammo: file.reserve_ammo,
+            reload_time: file.reload_time,
+            pierce: file.pierce.max(1),
+            ricochets: file.ricochets,
+            explosion: file.explosion,
+            homing: file.homing,
+            beam: file.beam,
+            sound: file.sound.map(|path| load_context.load(path)),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["weapon.ron"]
+    }
+}
+
+impl WeaponDefFile {
+    /// Reads a weapon and checks its timings, which end up in `Timer`s that
+    /// panic on negative or NaN seconds.
+    fn parse(bytes: &[u8]) -> Result<WeaponDefFile, WeaponDefLoaderError> {
+        let file: WeaponDefFile = ron::de::from_bytes(bytes)?;
+        let value = file.fire_interval;
+        if !value.is_finite() || value <= 0.0 {
+            let field = "fire_interval";
+            return Err(WeaponDefLoaderError::InvalidInterval { field, value });
+        }
+        for (field, value) in [
+            ("projectile_lifetime", file.projectile_lifetime),
+            ("reload_time", file.reload_time),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(WeaponDefLoaderError::InvalidDuration { field, value });
+            }
+        }
+
+        Ok(file)
+    }
+}
+
+fn default_pierce() -> u32 {
+    1
+}
+
+fn default_crit_multiplier() -> f32 {
+    2.0
+}
+
+const WEAPON_SLOT_KEYS: [KeyCode; MAX_WEAPON_SLOTS] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+pub fn spawn_weapon(
+    commands: &mut Commands,
+    handle: &GlobalTextureAtlas,
+    def: Handle<WeaponDef>,
+    active: bool,
+) -> Entity {
+    let mut weapon = commands.spawn((
+        SpriteBundle {
+            texture: handle.image.clone().unwrap(),
+            transform: Transform::from_scale(Vec3::splat(SPRITE_SCALE_FACTOR)),
+            visibility: if active {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            },
+            ..default()
+        },
+        TextureAtlas {
+            layout: handle.layout.clone().unwrap(),
+            index: 14,
+        },
+        Weapon(def),
+        WeaponTimer(Stopwatch::new()),
+        GameEntity,
+    ));
+    if active {
+        weapon.insert(ActiveWeapon);
+    }
+
+    weapon.id()
+}
+
+fn start_reload(
+    commands: &mut Commands,
+    weapon: Entity,
+    def: &WeaponDef,
+    audio: &GlobalAudioSource,
+) {
+    commands
+        .entity(weapon)
+        .insert(Reloading(Timer::from_seconds(
+            def.reload_time,
+            TimerMode::Once,
+        )));
+    commands.spawn((
+        AudioBundle {
+            source: audio.reload_effect.clone().unwrap(),
+            settings: PlaybackSettings::DESPAWN,
+        },
+        SoundEffect,
+    ));
+}
+
 fn despawn_old_bullets(
     mut commands: Commands,
-    bullet_query: Query<(&SpawnInstant, Entity), With<Bullet>>,
+    time: Res<Time>,
+    mut bullet_query: Query<(&mut Bullet, Entity)>,
 ) {
-    for (instant, entity) in bullet_query.iter() {
-        if instant.0.elapsed().as_secs_f32() > BULLET_TIME_SECS {
+    for (mut bullet, entity) in bullet_query.iter_mut() {
+        if bullet.lifetime.tick(time.delta()).finished() {
             commands.entity(entity).despawn();
         }
     }
 }
 
+fn update_weapon_sprite(
+    weapon_defs: Res<Assets<WeaponDef>>,
+    mut weapon_query: Query<(&mut TextureAtlas, &Weapon), With<Weapon>>,
+) {
+    for (mut atlas, weapon) in weapon_query.iter_mut() {
+        if let Some(def) = weapon_defs.get(&weapon.0) {
+            if atlas.index != def.sprite_index {
+                atlas.index = def.sprite_index;
+            }
+        }
+    }
+}
+
 fn update_weapon_transform(
     cursor_pos: Res<CursorPosition>,
     player_query: Query<&Transform, With<Player>>,
-    mut weapon_query: Query<&mut Transform, (With<Weapon>, Without<Player>)>,
+    mut weapon_query: Query<&mut Transform, (With<ActiveWeapon>, Without<Player>)>,
 ) {
     if player_query.is_empty() || weapon_query.is_empty() {
         return;
@@ -86,38 +412,185 @@ fn update_weapon_transform(
     weapon_transform.translation.z = 15.0;
 }
 
-fn handle_weapon_input(
+fn tick_weapon_timers(time: Res<Time>, mut weapon_query: Query<&mut WeaponTimer, With<Weapon>>) {
+    for mut weapon_timer in weapon_query.iter_mut() {
+        weapon_timer.0.tick(time.delta());
+    }
+}
+
+fn init_weapon_stats(
+    mut commands: Commands,
+    weapon_defs: Res<Assets<WeaponDef>>,
+    weapon_query: Query<(Entity, &Weapon), Without<Ammo>>,
+) {
+    for (entity, weapon) in weapon_query.iter() {
+        if let Some(def) = weapon_defs.get(&weapon.0) {
+            commands.entity(entity).insert((
+                Ammo {
+                    magazine: def.magazine_size,
+                    reserve: def.reserve_ammo,
+                },
+                Critical {
+                    chance: def.crit_chance,
+                    multiplier: def.crit_multiplier,
+                },
+            ));
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn handle_reload_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    weapon_defs: Res<Assets<WeaponDef>>,
+    audio: Res<GlobalAudioSource>,
+    weapon_query: Query<(Entity, &Weapon, &Ammo), (With<ActiveWeapon>, Without<Reloading>)>,
+) {
+    if weapon_query.is_empty() || !keyboard_input.just_pressed(KeyCode::KeyR) {
+        return;
+    }
+
+    let (entity, weapon, ammo) = weapon_query.single();
+    let Some(def) = weapon_defs.get(&weapon.0) else {
+        return;
+    };
+
+    if ammo.magazine < def.magazine_size && ammo.reserve > 0 {
+        start_reload(&mut commands, entity, def, &audio);
+    }
+}
+
+fn update_reloads(
     mut commands: Commands,
     time: Res<Time>,
-    mut weapon_query: Query<(&Transform, &mut WeaponTimer), With<Weapon>>,
+    weapon_defs: Res<Assets<WeaponDef>>,
+    mut weapon_query: Query<(Entity, &Weapon, &mut Ammo, &mut Reloading), With<Weapon>>,
+) {
+    for (entity, weapon, mut ammo, mut reloading) in weapon_query.iter_mut() {
+        if !reloading.tick(time.delta()).finished() {
+            continue;
+        }
+
+        if let Some(def) = weapon_defs.get(&weapon.0) {
+            let refill =
+                (def.magazine_size - ammo.magazine.min(def.magazine_size)).min(ammo.reserve);
+            ammo.magazine += refill;
+            ammo.reserve -= refill;
+        }
+        commands.entity(entity).remove::<Reloading>();
+    }
+}
+
+fn handle_weapon_switch_input(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut player_query: Query<&mut WeaponInventory, With<Player>>,
+    mut weapon_query: Query<&mut Visibility, With<Weapon>>,
+) {
+    let scroll: f32 = mouse_wheel_events.read().map(|event| event.y).sum();
+    if player_query.is_empty() {
+        return;
+    }
+
+    let mut inventory = player_query.single_mut();
+    let num_slots = inventory.slots.len();
+    if num_slots == 0 {
+        return;
+    }
+
+    let mut next = inventory.active;
+    for (slot, key) in WEAPON_SLOT_KEYS.iter().enumerate().take(num_slots) {
+        if keyboard_input.just_pressed(*key) {
+            next = slot;
+        }
+    }
+
+    // ctrl + scroll is reserved for camera zoom
+    let zoom_held = keyboard_input.pressed(KeyCode::ControlLeft)
+        || keyboard_input.pressed(KeyCode::ControlRight);
+    if !zoom_held {
+        if scroll > 0.0 {
+            next = (next + num_slots - 1) % num_slots;
+        } else if scroll < 0.0 {
+            next = (next + 1) % num_slots;
+        }
+    }
+
+    if next == inventory.active {
+        return;
+    }
+
+    let previous = inventory.slots[inventory.active];
+    let current = inventory.slots[next];
+    inventory.active = next;
+
+    commands.entity(previous).remove::<ActiveWeapon>();
+    commands.entity(current).insert(ActiveWeapon);
+    if let Ok(mut visibility) = weapon_query.get_mut(previous) {
+        *visibility = Visibility::Hidden;
+    }
+    if let Ok(mut visibility) = weapon_query.get_mut(current) {
+        *visibility = Visibility::Inherited;
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn handle_weapon_input(
+    mut commands: Commands,
+    mut weapon_query: Query<
+        (Entity, &Transform, &mut WeaponTimer, &Weapon, &mut Ammo),
+        (With<ActiveWeapon>, Without<Reloading>),
+    >,
+    player_query: Query<&PlayerUpgrades, With<Player>>,
+    power_ups: Res<PowerUps>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
+    weapon_defs: Res<Assets<WeaponDef>>,
     handle: Res<GlobalTextureAtlas>,
     audio: Res<GlobalAudioSource>,
 ) {
-    if weapon_query.is_empty() {
+    if weapon_query.is_empty() || player_query.is_empty() {
         return;
     }
 
-    let (weapon_transform, mut weapon_timer) = weapon_query.single_mut();
+    let (entity, weapon_transform, mut weapon_timer, weapon, mut ammo) = weapon_query.single_mut();
     let weapon_pos = weapon_transform.translation.truncate();
-    weapon_timer.0.tick(time.delta());
 
     if !mouse_button_input.pressed(MouseButton::Left) {
         return;
     }
 
+    let Some(def) = weapon_defs.get(&weapon.0) else {
+        return;
+    };
+
+    if ammo.magazine == 0 {
+        if ammo.reserve > 0 {
+            start_reload(&mut commands, entity, def, &audio);
+        }
+        return;
+    }
+
+    let upgrades = player_query.single();
     let mut rng = rand::thread_rng();
     let bullet_direction = weapon_transform.local_x();
-    if weapon_timer.0.elapsed_secs() >= BULLET_SPAWN_INTERVAL {
+    if weapon_timer.0.elapsed_secs()
+        >= def.fire_interval / (upgrades.fire_rate * power_ups.fire_rate_scale())
+    {
         weapon_timer.0.reset();
+        ammo.magazine -= 1;
 
-        for _ in 0..NUM_BULLETS_PER_SHOT {
-            let dir = vec3(
-                bullet_direction.x + rng.gen_range(-0.5..0.5),
-                bullet_direction.y + rng.gen_range(-0.5..0.5),
-                bullet_direction.z,
-            );
-            commands.spawn((
+        let pellets = if def.beam.is_some() { 0 } else { def.pellets };
+        for _ in 0..pellets {
+            let half_spread = def.spread / 2.0;
+            let angle = if half_spread > 0.0 {
+                rng.gen_range(-half_spread..half_spread)
+            } else {
+                0.0
+            };
+            let dir = Quat::from_rotation_z(angle) * *bullet_direction;
+            let mut bullet = commands.spawn((
                 SpriteBundle {
                     texture: handle.image.clone().unwrap(),
                     transform: Transform::from_translation(vec3(weapon_pos.x, weapon_pos.y, 1.0))
@@ -126,31 +599,256 @@ fn handle_weapon_input(
                 },
                 TextureAtlas {
                     layout: handle.layout.clone().unwrap(),
-                    index: 15,
+                    index: def.projectile_sprite_index,
+                },
+                Bullet {
+                    damage: def.damage * upgrades.damage,
+                    kind: def.damage_kind,
+                    source: entity,
+                    knockback: def.knockback,
+                    speed: def.projectile_speed,
+                    lifetime: Timer::from_seconds(def.projectile_lifetime, TimerMode::Once),
                 },
-                Bullet,
                 BulletDirection(dir),
-                SpawnInstant(Instant::now()),
+                Faction::Player,
+                Pierce(def.pierce),
+                HitEnemies::default(),
                 GameEntity,
             ));
+            if def.ricochets > 0 {
+                bullet.insert(Ricochet(def.ricochets));
+            }
+            if let Some(explosion) = def.explosion {
+                bullet.insert(explosion);
+            }
+            if let Some(homing) = def.homing {
+                bullet.insert(homing);
+            }
         }
         commands.spawn((
             AudioBundle {
-                source: audio.weapon_effect.clone().unwrap(),
+                source: def
+                    .sound
+                    .clone()
+                    .unwrap_or_else(|| audio.weapon_effect.clone().unwrap()),
                 settings: PlaybackSettings::DESPAWN,
             },
             SoundEffect,
         ));
+
+        if ammo.magazine == 0 && ammo.reserve > 0 {
+            start_reload(&mut commands, entity, def, &audio);
+        }
     }
 }
 
-fn update_bullets(mut bullet_query: Query<(&mut Transform, &BulletDirection), With<Bullet>>) {
+#[allow(clippy::type_complexity)]
+fn update_homing_bullets(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    tree: Res<EnemyKdTree>,
+    enemy_query: Query<(&Transform, &Health), (With<Enemy>, Without<Bullet>)>,
+    mut bullet_query: Query<(&Transform, &mut BulletDirection, &mut Homing), With<Bullet>>,
+) {
+    let is_alive = |entity: Entity| {
+        enemy_query
+            .get(entity)
+            .is_ok_and(|(_, health)| health.0 > 0.0)
+    };
+
+    for (transform, mut direction, mut homing) in bullet_query.iter_mut() {
+        let pos = transform.translation.truncate();
+
+        // drop targets that died or were despawned since the last frame
+        if homing.target.is_some_and(|target| !is_alive(target)) {
+            homing.target = None;
+        }
+        if homing.target.is_none() {
+            homing.target = tree
+                .nearest(pos)
+                .map(|nearest| nearest.entity)
+                .filter(|entity| is_alive(*entity));
+        }
+
+        let Some((target_transform, _)) = homing.target.and_then(|e| enemy_query.get(e).ok())
+        else {
+            continue;
+        };
+
+        let current = direction.0.truncate().normalize_or_zero();
+        // the tree can hand back a copy past the seam, aim at whichever is closer
+        let target = target_transform.translation.truncate();
+        let desired = wrapped_delta(pos, target, settings.wraps_world()).normalize_or_zero();
+        if current == Vec2::ZERO || desired == Vec2::ZERO {
+            continue;
+        }
+
+        let max_turn = homing.turn_rate * time.delta_seconds();
+        let turn = current.angle_between(desired).clamp(-max_turn, max_turn);
+        direction.0 = Vec2::from_angle(turn).rotate(current).extend(0.0);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_weapon_beams(
+    mut commands: Commands,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    weapon_defs: Res<Assets<WeaponDef>>,
+    player_query: Query<&PlayerUpgrades, With<Player>>,
+    weapon_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Weapon,
+            Option<&Ammo>,
+            Has<ActiveWeapon>,
+            Has<Reloading>,
+            Has<Beam>,
+        ),
+        With<Weapon>,
+    >,
+    obstacle_query: Query<(&Transform, &Obstacle), Without<Weapon>>,
+) {
+    let damage_scale = player_query
+        .get_single()
+        .map_or(1.0, |upgrades| upgrades.damage);
+    for (entity, transform, weapon, ammo, active, reloading, has_beam) in weapon_query.iter() {
+        let beam = weapon_defs
+            .get(&weapon.0)
+            .and_then(|def| def.beam.map(|beam_def| (beam_def, def.damage_kind)));
+        let firing = active
+            && !reloading
+            && mouse_button_input.pressed(MouseButton::Left)
+            && ammo.is_some_and(|ammo| ammo.magazine > 0);
+
+        match beam {
+            Some((beam_def, kind)) if firing => {
+                let start = transform.translation.truncate();
+                let direction = transform.local_x().truncate().normalize_or_zero();
+                let end = start + direction * beam_def.range;
+                // stop at the first obstacle, like bullets do
+                let reach = obstacle_query
+                    .iter()
+                    .filter_map(|(obstacle_transform, obstacle)| {
+                        let center = obstacle_transform.translation.truncate();
+                        obstacle.segment_hit(center, start, end)
+                    })
+                    .fold(1.0, f32::min);
+                commands.entity(entity).insert(Beam {
+                    start,
+                    end: start.lerp(end, reach),
+                    width: beam_def.width,
+                    damage_per_second: beam_def.damage_per_second * damage_scale,
+                    kind,
+                });
+            }
+            _ if has_beam => {
+                commands.entity(entity).remove::<Beam>();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw_weapon_beams(mut gizmos: Gizmos, beam_query: Query<&Beam>) {
+    for beam in beam_query.iter() {
+        gizmos.line_2d(beam.start, beam.end, Color::srgb(1.0, 0.3, 0.3));
+    }
+}
+
+fn update_bullets(
+    mut bullet_query: Query<
+        (
+            &mut Transform,
+            &Bullet,
+            &mut BulletDirection,
+            Option<&mut Ricochet>,
+        ),
+        With<Bullet>,
+    >,
+    settings: Res<Settings>,
+) {
     if bullet_query.is_empty() {
         return;
     }
 
-    for (mut transform, direction) in bullet_query.iter_mut() {
-        transform.translation += direction.0.normalize() * Vec3::splat(BULLET_SPEED);
+    for (mut transform, bullet, mut direction, ricochet) in bullet_query.iter_mut() {
+        transform.translation += direction.0.normalize() * Vec3::splat(bullet.speed);
         transform.translation.z = 10.0;
+
+        let Some(mut ricochet) = ricochet else {
+            continue;
+        };
+        // only walls have an edge to bounce off
+        if ricochet.0 == 0 || settings.world_edges() != WorldEdges::Walled {
+            continue;
+        }
+
+        let pos = &mut transform.translation;
+        let mut bounced = false;
+        if pos.x.abs() > WORLD_WIDTH {
+            pos.x = pos.x.clamp(-WORLD_WIDTH, WORLD_WIDTH);
+            direction.0.x = -direction.0.x;
+            bounced = true;
+        }
+        if pos.y.abs() > WORLD_HEIGHT {
+            pos.y = pos.y.clamp(-WORLD_HEIGHT, WORLD_HEIGHT);
+            direction.0.y = -direction.0.y;
+            bounced = true;
+        }
+        if bounced {
+            ricochet.0 -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RIFLE: &str = include_str!("../assets/weapons/rifle.weapon.ron");
+
+    fn rifle_with(field: &str, value: &str) -> Result<WeaponDefFile, WeaponDefLoaderError> {
+        let line = RIFLE
+            .lines()
+            .find(|line| line.trim_start().starts_with(&format!("{field}:")))
+            .unwrap();
+        let edited = RIFLE.replace(line, &format!("    {field}: {value},"));
+        WeaponDefFile::parse(edited.as_bytes())
+    }
+
+    #[test]
+    fn parses_bundled_weapons() {
+        for bytes in [
+            include_bytes!("../assets/weapons/rifle.weapon.ron").as_slice(),
+            include_bytes!("../assets/weapons/shotgun.weapon.ron"),
+            include_bytes!("../assets/weapons/grenade_launcher.weapon.ron"),
+            include_bytes!("../assets/weapons/missile_launcher.weapon.ron"),
+            include_bytes!("../assets/weapons/laser.weapon.ron"),
+        ] {
+            assert!(WeaponDefFile::parse(bytes).is_ok());
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_timings() {
+        for value in ["0.0", "-0.1", "NaN", "inf"] {
+            assert!(matches!(
+                rifle_with("fire_interval", value),
+                Err(WeaponDefLoaderError::InvalidInterval {
+                    field: "fire_interval",
+                    ..
+                })
+            ));
+        }
+        for field in ["projectile_lifetime", "reload_time"] {
+            for value in ["-1.0", "NaN"] {
+                assert!(matches!(
+                    rifle_with(field, value),
+                    Err(WeaponDefLoaderError::InvalidDuration { field: f, .. }) if f == field
+                ));
+            }
+            assert!(rifle_with(field, "0.0").is_ok());
+        }
     }
 }
@@ -1,36 +1,120 @@
 use animation::AnimationTimer;
-use bevy::{math::vec3, prelude::*, time::Stopwatch};
+use bevy::utils::HashMap;
+use bevy::{
+    math::vec2,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use passive::PassiveWeapons;
 use rand::Rng;
-use weapon::{Weapon, WeaponTimer};
+use weapon::{spawn_weapon, Bullet, Ricochet, WeaponInventory};
 
 use crate::*;
-use player::{Health, Player, PlayerState};
-use state::GameState;
+use arena::{spawn_arena, Arena};
+use damage::{Health, IFrames};
+use enemy::Enemy;
+use experience::XpGem;
+use pickup::Pickup;
+use player::{Player, PlayerState, PlayerUpgrades};
+use state::{GameState, InRun};
+use worldgen::{chunk_coord, generate_chunk, ChunkData};
 
 pub struct WorldPlugin;
 
 #[derive(Component)]
 pub struct GameEntity;
 
+/// Seed the current map was generated from.
+#[derive(Resource, Default, Clone, Copy)]
+pub struct WorldSeed(pub u64);
+
+/// Root of one streamed in chunk of the map. The ground and decorations are
+/// children and go away with it.
+#[derive(Component)]
+pub struct Chunk {
+    pub coord: IVec2,
+}
+
+#[derive(Resource, Default)]
+pub struct LoadedChunks {
+    chunks: HashMap<IVec2, Entity>,
+    /// Obstacles placed for each chunk. They cover a wider area than the
+    /// ground and are generated again from the seed when a chunk comes back.
+    obstacles: HashMap<IVec2, Vec<Entity>>,
+    /// Off while playing a hand-authored arena.
+    streaming: bool,
+}
+
+/// A copy of `source` drawn one world size over in the direction of `shift`,
+/// so things just past the seam of a wrapping world are visible from this
+/// side.
+#[derive(Component)]
+pub struct WrapGhost {
+    source: Entity,
+    shift: IVec2,
+}
+
+/// A static axis-aligned box that blocks movement and bullets.
+#[derive(Component)]
+pub struct Obstacle {
+    pub half_size: Vec2,
+}
+
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            OnEnter(GameState::GameInit),
-            (init_world, spawn_world_decorations),
-        )
-        .add_systems(OnExit(GameState::InGame), despawn_all_game_entities);
+        app.init_resource::<WorldSeed>()
+            .init_resource::<LoadedChunks>()
+            .add_systems(
+                OnEnter(GameState::GameInit),
+                (
+                    init_world,
+                    (reset_world_map, (stream_world_chunks, spawn_selected_arena)).chain(),
+                    spawn_world_border,
+                ),
+            )
+            .add_systems(
+                Update,
+                stream_world_chunks.run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                PostUpdate,
+                (enforce_world_bounds, update_wrap_ghosts)
+                    .chain()
+                    .before(TransformSystem::TransformPropagate)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(InRun), despawn_all_game_entities);
     }
 }
 
 fn init_world(
     mut commands: Commands,
     handle: Res<GlobalTextureAtlas>,
+    settings: Res<Settings>,
+    global_arenas: Res<GlobalArenas>,
+    arenas: Res<Assets<Arena>>,
+    weapon_defs: Res<GlobalWeaponDefs>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
+    let weapons = weapon_defs
+        .starting_weapons
+        .iter()
+        .take(MAX_WEAPON_SLOTS)
+        .enumerate()
+        .map(|(slot, def)| spawn_weapon(&mut commands, &handle, def.clone(), slot == 0))
+        .collect();
+    let start = global_arenas
+        .selected(&settings, &arenas)
+        .map_or(Vec2::ZERO, |arena| arena.player_start());
+
     commands.spawn((
         SpriteBundle {
             texture: handle.image.clone().unwrap(),
-            transform: Transform::from_scale(Vec3::splat(SPRITE_SCALE_FACTOR)),
+            transform: Transform::from_translation(start.extend(10.0))
+                .with_scale(Vec3::splat(SPRITE_SCALE_FACTOR)),
             ..default()
         },
         TextureAtlas {
@@ -40,46 +124,534 @@ fn init_world(
         AnimationTimer(Timer::from_seconds(0.15, TimerMode::Repeating)),
         Player,
         Health(PLAYER_HEALTH),
+        IFrames(PLAYER_IFRAME_SECS),
         PlayerState::default(),
-        GameEntity,
-    ));
-
-    commands.spawn((
-        SpriteBundle {
-            texture: handle.image.clone().unwrap(),
-            transform: Transform::from_scale(Vec3::splat(SPRITE_SCALE_FACTOR)),
-            ..default()
+        PlayerUpgrades::default(),
+        WeaponInventory {
+            slots: weapons,
+            active: 0,
         },
-        TextureAtlas {
-            layout: handle.layout.clone().unwrap(),
-            index: 14,
+        PassiveWeapons {
+            blade_level: 1,
+            aura_level: 1,
         },
-        Weapon,
-        WeaponTimer(Stopwatch::new()),
         GameEntity,
     ));
 
     next_state.set(GameState::InGame);
 }
 
-fn spawn_world_decorations(mut commands: Commands, handle: Res<GlobalTextureAtlas>) {
-    let mut rng = rand::thread_rng();
-    for _ in 0..NUM_WORLD_DECORATIONS {
-        let x = rng.gen_range(-WORLD_WIDTH..WORLD_WIDTH);
-        let y = rng.gen_range(-WORLD_HEIGHT..WORLD_HEIGHT);
+impl Obstacle {
+    /// How far a circle at `pos` has to move to stop overlapping the obstacle
+    /// centred on `center`, or `None` when they don't overlap.
+    pub fn push_out(&self, center: Vec2, pos: Vec2, radius: f32) -> Option<Vec2> {
+        let local = pos - center;
+        let closest = local.clamp(-self.half_size, self.half_size);
+        let offset = local - closest;
+        let distance_squared = offset.length_squared();
+        if distance_squared >= radius * radius {
+            return None;
+        }
+
+        if distance_squared > 0.0 {
+            let distance = distance_squared.sqrt();
+            return Some(offset / distance * (radius - distance));
+        }
+        // the centre is inside the box, leave through the nearest side
+        let depth = self.half_size - local.abs() + Vec2::splat(radius);
+        if depth.x < depth.y {
+            Some(vec2(depth.x * local.x.signum(), 0.0))
+        } else {
+            Some(vec2(0.0, depth.y * local.y.signum()))
+        }
+    }
+
+    pub fn contains(&self, center: Vec2, pos: Vec2) -> bool {
+        let local = (pos - center).abs();
+        local.x <= self.half_size.x && local.y <= self.half_size.y
+    }
+
+    /// How far along the segment from `start` to `end` it first touches the
+    /// obstacle centred on `center`, as a fraction of its length, or `None`
+    /// when it misses.
+    pub fn segment_hit(&self, center: Vec2, start: Vec2, end: Vec2) -> Option<f32> {
+        let (min, max) = (center - self.half_size, center + self.half_size);
+        let delta = end - start;
+        let (mut enter, mut exit) = (0.0_f32, 1.0_f32);
+        for axis in 0..2 {
+            if delta[axis] == 0.0 {
+                if start[axis] < min[axis] || start[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let near = (min[axis] - start[axis]) / delta[axis];
+            let far = (max[axis] - start[axis]) / delta[axis];
+            enter = enter.max(near.min(far));
+            exit = exit.min(near.max(far));
+            if enter > exit {
+                return None;
+            }
+        }
+        Some(enter)
+    }
+}
+
+fn reset_world_map(
+    settings: Res<Settings>,
+    global_arenas: Res<GlobalArenas>,
+    arenas: Res<Assets<Arena>>,
+    mut seed: ResMut<WorldSeed>,
+    mut chunks: ResMut<LoadedChunks>,
+) {
+    chunks.chunks.clear();
+    chunks.obstacles.clear();
+    chunks.streaming = global_arenas.selected(&settings, &arenas).is_none();
+    if !chunks.streaming {
+        return;
+    }
+    seed.0 = settings
+        .world_seed
+        .unwrap_or_else(|| rand::thread_rng().gen());
+    info!("generating world with seed {}", seed.0);
+}
+
+fn spawn_selected_arena(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    settings: Res<Settings>,
+    global_arenas: Res<GlobalArenas>,
+    arenas: Res<Assets<Arena>>,
+) {
+    if let Some(arena) = global_arenas.selected(&settings, &arenas) {
+        info!("building arena {}", arena.name);
+        spawn_arena(&mut commands, &mut images, arena);
+    }
+}
+
+/// A texture with one RGBA pixel per ground tile, top row first.
+pub fn ground_image(width: u32, height: u32, pixels: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+/// The area the world is confined to, `None` for an endless map.
+pub fn world_rect(settings: &Settings) -> Option<Rect> {
+    if settings.world_edges() == WorldEdges::Endless {
+        return None;
+    }
+
+    Some(Rect::from_center_half_size(
+        Vec2::ZERO,
+        vec2(WORLD_WIDTH, WORLD_HEIGHT),
+    ))
+}
+
+/// Spawns the chunks around the camera's view and despawns the ones that
+/// are well out of it. The margins differ so a chunk on the edge doesn't flip
+/// every frame. Obstacles are placed further out and never despawned.
+fn stream_world_chunks(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    handle: Res<GlobalTextureAtlas>,
+    settings: Res<Settings>,
+    seed: Res<WorldSeed>,
+    mut chunks: ResMut<LoadedChunks>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<Camera>>,
+) {
+    if camera_query.is_empty() || !chunks.streaming {
+        return;
+    }
+
+    let (transform, projection) = camera_query.single();
+    let center = transform.translation.truncate();
+    let half_view = projection.area.half_size().max(vec2(WW, WH) / 2.0);
+    let load = Rect::from_center_half_size(center, half_view + CHUNK_LOAD_MARGIN);
+    let unload = Rect::from_center_half_size(center, half_view + CHUNK_UNLOAD_MARGIN);
+    let place = Rect::from_center_half_size(center, half_view + OBSTACLE_LOAD_MARGIN);
+    let unplace = Rect::from_center_half_size(center, half_view + OBSTACLE_UNLOAD_MARGIN);
+    let (load_min, load_max) = (chunk_coord(load.min), chunk_coord(load.max));
+    let world = world_rect(&settings);
+
+    chunks.chunks.retain(|coord, entity| {
+        let keep = !ChunkData::bounds(*coord).intersect(unload).is_empty();
+        if !keep {
+            commands.entity(*entity).despawn_recursive();
+        }
+        keep
+    });
+    // the flow field keeps their cells blocked, which still holds once the
+    // same obstacles are generated again
+    chunks.obstacles.retain(|coord, entities| {
+        let keep = !ChunkData::bounds(*coord).intersect(unplace).is_empty();
+        if !keep {
+            for entity in entities.iter() {
+                commands.entity(*entity).despawn();
+            }
+        }
+        keep
+    });
+
+    let (min, max) = (chunk_coord(place.min), chunk_coord(place.max));
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let coord = IVec2::new(x, y);
+            let in_view = coord.cmpge(load_min).all() && coord.cmple(load_max).all();
+            let spawn_ground = in_view && !chunks.chunks.contains_key(&coord);
+            let spawn_obstacles = !chunks.obstacles.contains_key(&coord);
+            if !spawn_ground && !spawn_obstacles {
+                continue;
+            }
+
+            let chunk = generate_chunk(seed.0, coord, world);
+            if spawn_obstacles {
+                let entities = spawn_chunk_obstacles(&mut commands, &chunk);
+                chunks.obstacles.insert(coord, entities);
+            }
+            if spawn_ground {
+                let entity = spawn_chunk(&mut commands, &mut images, &handle, seed.0, &chunk);
+                chunks.chunks.insert(coord, entity);
+            }
+        }
+    }
+}
+
+fn spawn_chunk(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    handle: &GlobalTextureAtlas,
+    seed: u64,
+    chunk: &ChunkData,
+) -> Entity {
+    let ground = ground_image(
+        CHUNK_TILES as u32,
+        CHUNK_TILES as u32,
+        chunk.ground_pixels(seed),
+    );
+    let ground = images.add(ground);
+
+    // the root sits at the origin so children keep world space transforms
+    commands
+        .spawn((
+            SpatialBundle::default(),
+            Chunk { coord: chunk.coord },
+            GameEntity,
+        ))
+        .with_children(|parent| {
+            parent.spawn(SpriteBundle {
+                texture: ground,
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(CHUNK_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(chunk.center().extend(-1.0)),
+                ..default()
+            });
+
+            for decoration in chunk.decorations.iter() {
+                parent.spawn((
+                    SpriteBundle {
+                        texture: handle.image.clone().unwrap(),
+                        transform: Transform::from_translation(decoration.pos.extend(0.0))
+                            .with_scale(Vec3::splat(SPRITE_SCALE_FACTOR)),
+                        ..default()
+                    },
+                    TextureAtlas {
+                        layout: handle.layout.clone().unwrap(),
+                        index: decoration.index,
+                    },
+                ));
+            }
+        })
+        .id()
+}
+
+fn spawn_chunk_obstacles(commands: &mut Commands, chunk: &ChunkData) -> Vec<Entity> {
+    let mut entities = Vec::with_capacity(chunk.obstacles.len());
+    for obstacle in chunk.obstacles.iter() {
+        let entity = commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: obstacle.kind.color(),
+                    custom_size: Some(obstacle.size),
+                    ..default()
+                },
+                transform: Transform::from_translation(obstacle.pos.extend(0.5)),
+                ..default()
+            },
+            Obstacle {
+                half_size: obstacle.size / 2.0,
+            },
+            GameEntity,
+        ));
+        entities.push(entity.id());
+    }
+    entities
+}
+
+/// Keeps a circle of `radius` at `pos` inside the arena.
+pub fn clamp_to_world(pos: Vec2, radius: f32) -> Vec2 {
+    let half_size = vec2(WORLD_WIDTH - radius, WORLD_HEIGHT - radius).max(Vec2::ZERO);
+    pos.clamp(-half_size, half_size)
+}
+
+/// Moves a point that left the arena to the matching spot past the opposite
+/// edge.
+pub fn wrap_to_world(pos: Vec2) -> Vec2 {
+    let wrap = |v: f32, half: f32| (v + half).rem_euclid(2.0 * half) - half;
+    vec2(wrap(pos.x, WORLD_WIDTH), wrap(pos.y, WORLD_HEIGHT))
+}
+
+/// The shortest offset from `from` to `to`, across the edge when the arena
+/// wraps around.
+pub fn wrapped_delta(from: Vec2, to: Vec2, wrap: bool) -> Vec2 {
+    let delta = to - from;
+    if !wrap {
+        return delta;
+    }
+    let size = vec2(2.0 * WORLD_WIDTH, 2.0 * WORLD_HEIGHT);
+    delta - (delta / size).round() * size
+}
+
+/// Where a point within `margin` of the edges of a wrapping arena also
+/// appears past the opposite edges. Spatial indexes store these copies so
+/// queries near the seam find things on the other side.
+pub fn wrapped_copies(pos: Vec2, margin: f32) -> Vec<Vec2> {
+    let shift = |v: f32, half: f32| {
+        if v > half - margin {
+            -2.0 * half
+        } else if v < margin - half {
+            2.0 * half
+        } else {
+            0.0
+        }
+    };
+    let (dx, dy) = (shift(pos.x, WORLD_WIDTH), shift(pos.y, WORLD_HEIGHT));
+    let mut copies = Vec::new();
+    if dx != 0.0 {
+        copies.push(pos + vec2(dx, 0.0));
+    }
+    if dy != 0.0 {
+        copies.push(pos + vec2(0.0, dy));
+    }
+    if dx != 0.0 && dy != 0.0 {
+        copies.push(pos + vec2(dx, dy));
+    }
+    copies
+}
+
+pub fn is_outside_world(pos: Vec2) -> bool {
+    pos.x.abs() > WORLD_WIDTH || pos.y.abs() > WORLD_HEIGHT
+}
+
+fn spawn_world_border(mut commands: Commands, settings: Res<Settings>) {
+    // a solid wall around a walled arena, a thin glowing seam around a wrapping one
+    let (thickness, color) = match settings.world_edges() {
+        WorldEdges::Walled => (WORLD_BORDER_THICKNESS, Color::srgb(0.15, 0.12, 0.1)),
+        WorldEdges::Wrap => (
+            WORLD_WRAP_BORDER_THICKNESS,
+            Color::srgba(0.3, 0.6, 1.0, 0.6),
+        ),
+        WorldEdges::Endless => return,
+    };
+    let width = 2.0 * (WORLD_WIDTH + thickness);
+    let height = 2.0 * WORLD_HEIGHT;
+    let edges = [
+        (
+            vec2(0.0, WORLD_HEIGHT + thickness / 2.0),
+            vec2(width, thickness),
+        ),
+        (
+            vec2(0.0, -WORLD_HEIGHT - thickness / 2.0),
+            vec2(width, thickness),
+        ),
+        (
+            vec2(WORLD_WIDTH + thickness / 2.0, 0.0),
+            vec2(thickness, height),
+        ),
+        (
+            vec2(-WORLD_WIDTH - thickness / 2.0, 0.0),
+            vec2(thickness, height),
+        ),
+    ];
+    for (pos, size) in edges {
         commands.spawn((
             SpriteBundle {
-                texture: handle.image.clone().unwrap(),
-                transform: Transform::from_translation(vec3(x, y, 0.0))
-                    .with_scale(Vec3::splat(SPRITE_SCALE_FACTOR)),
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(0.5)),
                 ..default()
             },
-            TextureAtlas {
-                layout: handle.layout.clone().unwrap(),
-                index: rng.gen_range(12..=13),
+            GameEntity,
+        ));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn enforce_world_bounds(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+    mut enemy_query: Query<(&mut Transform, &Enemy), Without<Player>>,
+    mut bullet_query: Query<
+        (Entity, &mut Transform, Option<&Ricochet>),
+        (With<Bullet>, Without<Player>, Without<Enemy>),
+    >,
+) {
+    // new enemies are confined here too, before they're ever drawn
+    if settings.world_edges() == WorldEdges::Endless {
+        return;
+    }
+    let confine = |transform: &mut Transform, radius: f32| {
+        let pos = transform.translation.truncate();
+        let confined = if settings.wraps_world() {
+            wrap_to_world(pos)
+        } else {
+            clamp_to_world(pos, radius)
+        };
+        transform.translation = confined.extend(transform.translation.z);
+    };
+
+    for mut transform in player_query.iter_mut() {
+        confine(&mut transform, PLAYER_COLLISION_RADIUS);
+    }
+    for (mut transform, enemy) in enemy_query.iter_mut() {
+        confine(&mut transform, enemy.kind.stats().collision_radius);
+    }
+
+    for (entity, mut transform, ricochet) in bullet_query.iter_mut() {
+        let pos = transform.translation.truncate();
+        if !is_outside_world(pos) {
+            continue;
+        }
+        if settings.wraps_world() {
+            transform.translation = wrap_to_world(pos).extend(transform.translation.z);
+        } else if ricochet.is_none_or(|ricochet| ricochet.0 == 0) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Which copies of something at `pos` past the seam fall within `view`, as
+/// steps of one world size.
+pub fn wrap_ghost_shifts(pos: Vec2, view: Rect) -> Vec<IVec2> {
+    let size = vec2(2.0 * WORLD_WIDTH, 2.0 * WORLD_HEIGHT);
+    let mut shifts = Vec::new();
+    for y in -1..=1 {
+        for x in -1..=1 {
+            let shift = IVec2::new(x, y);
+            if shift != IVec2::ZERO && view.contains(pos + shift.as_vec2() * size) {
+                shifts.push(shift);
+            }
+        }
+    }
+    shifts
+}
+
+/// Keeps a ghost sprite for everything whose copy past the seam is on screen,
+/// and despawns the ones that scrolled out of view.
+#[allow(clippy::type_complexity)]
+fn update_wrap_ghosts(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<Camera>>,
+    source_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Sprite,
+            &Handle<Image>,
+            Option<&TextureAtlas>,
+        ),
+        (
+            Or<(
+                With<Enemy>,
+                With<Bullet>,
+                With<Obstacle>,
+                With<Pickup>,
+                With<XpGem>,
+            )>,
+            Without<WrapGhost>,
+        ),
+    >,
+    mut ghost_query: Query<
+        (
+            Entity,
+            &WrapGhost,
+            &mut Transform,
+            &mut Sprite,
+            Option<&mut TextureAtlas>,
+        ),
+        Without<Camera>,
+    >,
+) {
+    if !settings.wraps_world() || camera_query.is_empty() {
+        return;
+    }
+
+    let (camera, projection) = camera_query.single();
+    let half_view = projection.area.half_size().max(vec2(WW, WH) / 2.0);
+    let view = Rect::from_center_half_size(
+        camera.translation.truncate(),
+        half_view + WORLD_WRAP_GHOST_MARGIN,
+    );
+    let size = vec2(2.0 * WORLD_WIDTH, 2.0 * WORLD_HEIGHT);
+
+    let mut wanted = HashMap::new();
+    for (entity, transform, ..) in source_query.iter() {
+        for shift in wrap_ghost_shifts(transform.translation.truncate(), view) {
+            wanted.insert((entity, shift), transform);
+        }
+    }
+
+    for (ghost, wrap_ghost, mut transform, mut sprite, atlas) in ghost_query.iter_mut() {
+        let key = (wrap_ghost.source, wrap_ghost.shift);
+        let Some(source) = wanted.remove(&key) else {
+            commands.entity(ghost).despawn();
+            continue;
+        };
+        let Ok((_, _, source_sprite, _, source_atlas)) = source_query.get(wrap_ghost.source) else {
+            continue;
+        };
+        *transform = *source;
+        transform.translation += (wrap_ghost.shift.as_vec2() * size).extend(0.0);
+        *sprite = source_sprite.clone();
+        if let (Some(mut atlas), Some(source_atlas)) = (atlas, source_atlas) {
+            atlas.index = source_atlas.index;
+        }
+    }
+
+    for ((source, shift), _) in wanted {
+        let Ok((_, transform, sprite, texture, atlas)) = source_query.get(source) else {
+            continue;
+        };
+        let mut transform = *transform;
+        transform.translation += (shift.as_vec2() * size).extend(0.0);
+        let mut ghost = commands.spawn((
+            SpriteBundle {
+                sprite: sprite.clone(),
+                texture: texture.clone(),
+                transform,
+                ..default()
             },
+            WrapGhost { source, shift },
             GameEntity,
         ));
+        if let Some(atlas) = atlas {
+            ghost.insert(atlas.clone());
+        }
     }
 }
 
@@ -95,3 +667,74 @@ fn despawn_all_game_entities(
         commands.entity(entity).despawn_recursive();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_stop_at_the_obstacle_edge() {
+        let obstacle = Obstacle {
+            half_size: vec2(10.0, 20.0),
+        };
+        let center = vec2(50.0, 0.0);
+        let hit = |start, end| obstacle.segment_hit(center, start, end);
+
+        assert_eq!(hit(Vec2::ZERO, vec2(100.0, 0.0)), Some(0.4));
+        assert_eq!(hit(vec2(100.0, 0.0), Vec2::ZERO), Some(0.4));
+        assert_eq!(hit(vec2(50.0, -40.0), vec2(50.0, 60.0)), Some(0.2));
+        // starting inside blocks the whole segment
+        assert_eq!(hit(center, vec2(200.0, 0.0)), Some(0.0));
+        // falling short, passing by and running alongside all miss
+        assert_eq!(hit(Vec2::ZERO, vec2(30.0, 0.0)), None);
+        assert_eq!(hit(vec2(0.0, 30.0), vec2(100.0, 30.0)), None);
+        assert_eq!(hit(vec2(0.0, 0.0), vec2(30.0, 100.0)), None);
+    }
+
+    #[test]
+    fn wrapped_delta_crosses_the_seam() {
+        let (from, to) = (
+            vec2(WORLD_WIDTH - 10.0, 0.0),
+            vec2(-WORLD_WIDTH + 10.0, 5.0),
+        );
+        assert_eq!(wrapped_delta(from, to, true), vec2(20.0, 5.0));
+        assert_eq!(
+            wrapped_delta(from, to, false),
+            vec2(-2.0 * WORLD_WIDTH + 20.0, 5.0)
+        );
+
+        let (from, to) = (
+            vec2(0.0, -WORLD_HEIGHT + 1.0),
+            vec2(0.0, WORLD_HEIGHT - 1.0),
+        );
+        assert_eq!(wrapped_delta(from, to, true), vec2(0.0, -2.0));
+    }
+
+    #[test]
+    fn wrapped_copies_show_up_past_the_seam() {
+        let pos = vec2(WORLD_WIDTH - 10.0, WORLD_HEIGHT - 10.0);
+        let copies = wrapped_copies(pos, 50.0);
+        assert_eq!(copies.len(), 3);
+        assert!(copies.contains(&vec2(-WORLD_WIDTH - 10.0, WORLD_HEIGHT - 10.0)));
+        assert!(copies.contains(&vec2(-WORLD_WIDTH - 10.0, -WORLD_HEIGHT - 10.0)));
+        assert!(wrapped_copies(Vec2::ZERO, 50.0).is_empty());
+    }
+
+    #[test]
+    fn ghosts_are_drawn_for_things_across_the_seam() {
+        // looking over the right edge at an enemy just past the left one
+        let view = Rect::from_center_half_size(vec2(WORLD_WIDTH - 100.0, 0.0), vec2(600.0, 450.0));
+        assert_eq!(
+            wrap_ghost_shifts(vec2(-WORLD_WIDTH + 50.0, 10.0), view),
+            vec![IVec2::X]
+        );
+        assert!(wrap_ghost_shifts(vec2(WORLD_WIDTH - 50.0, 10.0), view).is_empty());
+        assert!(wrap_ghost_shifts(Vec2::ZERO, view).is_empty());
+
+        // a corner view sees the diagonal copy too
+        let corner =
+            Rect::from_center_half_size(vec2(WORLD_WIDTH, WORLD_HEIGHT), vec2(600.0, 450.0));
+        let shifts = wrap_ghost_shifts(vec2(-WORLD_WIDTH + 50.0, -WORLD_HEIGHT + 50.0), corner);
+        assert_eq!(shifts, vec![IVec2::new(1, 1)]);
+    }
+}
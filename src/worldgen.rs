@@ -0,0 +1,304 @@
+use bevy::math::{ivec2, vec2};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::*;
+
+/// Ground type of a tile, picked from elevation and moisture noise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Biome {
+    Meadow,
+    Forest,
+    Badlands,
+    Desert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ObstacleKind {
+    Rock,
+    Tree,
+    Wall,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decoration {
+    pub pos: Vec2,
+    /// Index into the sprite sheet.
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObstacleSpawn {
+    pub pos: Vec2,
+    pub size: Vec2,
+    pub kind: ObstacleKind,
+}
+
+/// Everything placed in one chunk of the map. The same seed and chunk always
+/// produce the same data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkData {
+    pub coord: IVec2,
+    /// Biome of every tile, row by row starting at the bottom.
+    pub tiles: Vec<Biome>,
+    pub decorations: Vec<Decoration>,
+    pub obstacles: Vec<ObstacleSpawn>,
+}
+
+const MOISTURE_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+const SHADE_SALT: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const CHUNK_SALT: u64 = 0x1656_67b1_9e37_79f9;
+
+impl Biome {
+    pub fn at(seed: u64, pos: Vec2) -> Self {
+        let elevation = fractal_noise(seed, pos / BIOME_NOISE_SCALE);
+        let moisture = fractal_noise(seed ^ MOISTURE_SALT, pos / BIOME_NOISE_SCALE);
+        if elevation > 0.62 {
+            Biome::Badlands
+        } else if moisture > 0.58 {
+            Biome::Forest
+        } else if moisture < 0.4 {
+            Biome::Desert
+        } else {
+            Biome::Meadow
+        }
+    }
+
+    pub fn ground_color(self) -> [f32; 3] {
+        match self {
+            Biome::Meadow => [0.35, 0.5, 0.28],
+            Biome::Forest => [0.22, 0.38, 0.22],
+            Biome::Badlands => [0.42, 0.36, 0.32],
+            Biome::Desert => [0.7, 0.62, 0.42],
+        }
+    }
+
+    fn decoration_chance(self) -> f32 {
+        match self {
+            Biome::Meadow => 0.7,
+            Biome::Forest => 0.4,
+            Biome::Badlands => 0.15,
+            Biome::Desert => 0.1,
+        }
+    }
+
+    /// Chances of a rock, a tree and a wall for every obstacle attempt.
+    fn obstacle_chances(self) -> [f32; 3] {
+        match self {
+            Biome::Meadow => [0.1, 0.25, 0.05],
+            Biome::Forest => [0.05, 0.8, 0.0],
+            Biome::Badlands => [0.5, 0.0, 0.2],
+            Biome::Desert => [0.2, 0.02, 0.08],
+        }
+    }
+}
+
+impl ObstacleKind {
+    pub fn color(self) -> Color {
+        match self {
+            ObstacleKind::Rock => Color::srgb(0.45, 0.45, 0.5),
+            ObstacleKind::Tree => Color::srgb(0.2, 0.4, 0.15),
+            ObstacleKind::Wall => Color::srgb(0.4, 0.3, 0.2),
+        }
+    }
+}
+
+impl ChunkData {
+    pub fn origin(coord: IVec2) -> Vec2 {
+        coord.as_vec2() * CHUNK_SIZE
+    }
+
+    pub fn bounds(coord: IVec2) -> Rect {
+        let origin = Self::origin(coord);
+        Rect::from_corners(origin, origin + Vec2::splat(CHUNK_SIZE))
+    }
+
+    pub fn center(&self) -> Vec2 {
+        Self::bounds(self.coord).center()
+    }
+
+    /// RGBA pixels of the ground with one pixel per tile, top row first.
+    pub fn ground_pixels(&self, seed: u64) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.tiles.len() * 4);
+        for y in (0..CHUNK_TILES).rev() {
+            for x in 0..CHUNK_TILES {
+                let biome = self.tiles[y * CHUNK_TILES + x];
+                let tile = self.coord * CHUNK_TILES as i32 + ivec2(x as i32, y as i32);
+                // a little per tile variation so the ground doesn't look flat
+                let shade = 0.94 + 0.12 * lattice(seed ^ SHADE_SALT, tile.x, tile.y);
+                for channel in biome.ground_color() {
+                    pixels.push(((channel * shade).clamp(0.0, 1.0) * 255.0) as u8);
+                }
+                pixels.push(255);
+            }
+        }
+        pixels
+    }
+}
+
+/// The chunk containing `pos`.
+pub fn chunk_coord(pos: Vec2) -> IVec2 {
+    (pos / CHUNK_SIZE).floor().as_ivec2()
+}
+
+/// Generates a chunk of the map, leaving out anything that falls outside of
+/// `world`. An endless map passes `None`.
+pub fn generate_chunk(seed: u64, coord: IVec2, world: Option<Rect>) -> ChunkData {
+    let origin = ChunkData::origin(coord);
+    let mut tiles = Vec::with_capacity(CHUNK_TILES * CHUNK_TILES);
+    for y in 0..CHUNK_TILES {
+        for x in 0..CHUNK_TILES {
+            let pos = origin + (vec2(x as f32, y as f32) + 0.5) * GROUND_TILE_SIZE;
+            tiles.push(Biome::at(seed, pos));
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(hash(seed ^ CHUNK_SALT, coord.x, coord.y));
+    let random_pos = |rng: &mut StdRng| origin + vec2(rng.gen(), rng.gen()) * CHUNK_SIZE;
+    let in_world =
+        |min: Vec2, max: Vec2| world.is_none_or(|world| world.contains(min) && world.contains(max));
+
+    let mut decorations = Vec::new();
+    for _ in 0..DECORATION_ATTEMPTS_PER_CHUNK {
+        let pos = random_pos(&mut rng);
+        let index = rng.gen_range(12..=13);
+        if rng.gen::<f32>() < Biome::at(seed, pos).decoration_chance() && in_world(pos, pos) {
+            decorations.push(Decoration { pos, index });
+        }
+    }
+
+    let mut obstacles = Vec::new();
+    for _ in 0..OBSTACLE_ATTEMPTS_PER_CHUNK {
+        let pos = random_pos(&mut rng);
+        let [rock, tree, wall] = Biome::at(seed, pos).obstacle_chances();
+        let roll = rng.gen::<f32>();
+        let (kind, size) = if roll < rock {
+            (ObstacleKind::Rock, Vec2::splat(rng.gen_range(60.0..120.0)))
+        } else if roll < rock + tree {
+            (ObstacleKind::Tree, Vec2::splat(48.0))
+        } else if roll < rock + tree + wall {
+            let length = rng.gen_range(300.0..800.0);
+            let size = if rng.gen_bool(0.5) {
+                vec2(length, 40.0)
+            } else {
+                vec2(40.0, length)
+            };
+            (ObstacleKind::Wall, size)
+        } else {
+            continue;
+        };
+        // keep the player's spawn point clear
+        if pos.length() <= OBSTACLE_CLEAR_RADIUS + size.max_element()
+            || !in_world(pos - size / 2.0, pos + size / 2.0)
+        {
+            continue;
+        }
+        obstacles.push(ObstacleSpawn { pos, size, kind });
+    }
+
+    ChunkData {
+        coord,
+        tiles,
+        decorations,
+        obstacles,
+    }
+}
+
+fn hash(seed: u64, x: i32, y: i32) -> u64 {
+    // splitmix64 over the seed and both coordinates
+    let mut z = seed
+        ^ (x as u32 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (y as u32 as u64).wrapping_mul(0xd1b5_4a32_d192_ed03);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A random value in `0.0..1.0` for a lattice point.
+fn lattice(seed: u64, x: i32, y: i32) -> f32 {
+    (hash(seed, x, y) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn value_noise(seed: u64, pos: Vec2) -> f32 {
+    let cell = pos.floor();
+    let (x, y) = (cell.x as i32, cell.y as i32);
+    let t = pos - cell;
+    let t = t * t * (3.0 - 2.0 * t);
+    let bottom = lattice(seed, x, y).lerp(lattice(seed, x + 1, y), t.x);
+    let top = lattice(seed, x, y + 1).lerp(lattice(seed, x + 1, y + 1), t.x);
+    bottom.lerp(top, t.y)
+}
+
+/// Value noise summed over a few octaves, in `0.0..1.0`.
+fn fractal_noise(seed: u64, pos: Vec2) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max = 0.0;
+    let mut pos = pos;
+    for octave in 0..BIOME_NOISE_OCTAVES {
+        total += value_noise(seed.wrapping_add(octave as u64), pos) * amplitude;
+        max += amplitude;
+        amplitude *= 0.5;
+        pos *= 2.0;
+    }
+    total / max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> Rect {
+        Rect::from_center_half_size(Vec2::ZERO, vec2(WORLD_WIDTH, WORLD_HEIGHT))
+    }
+
+    /// Every chunk overlapping the world.
+    fn world_chunks() -> impl Iterator<Item = IVec2> {
+        let (min, max) = (chunk_coord(world().min), chunk_coord(world().max));
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| ivec2(x, y)))
+    }
+
+    #[test]
+    fn same_seed_gives_same_chunks() {
+        for coord in world_chunks() {
+            assert_eq!(
+                generate_chunk(42, coord, Some(world())),
+                generate_chunk(42, coord, Some(world()))
+            );
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_maps() {
+        let map = |seed| {
+            world_chunks()
+                .map(|coord| generate_chunk(seed, coord, Some(world())))
+                .collect::<Vec<_>>()
+        };
+        let (a, b) = (map(1), map(2));
+        assert!(a.iter().zip(&b).any(|(a, b)| a.tiles != b.tiles));
+        assert!(a
+            .iter()
+            .zip(&b)
+            .any(|(a, b)| a.decorations != b.decorations));
+        assert!(a.iter().zip(&b).any(|(a, b)| a.obstacles != b.obstacles));
+    }
+
+    #[test]
+    fn obstacles_stay_inside_the_world_and_off_the_spawn() {
+        for seed in 0..20 {
+            for coord in world_chunks() {
+                for obstacle in generate_chunk(seed, coord, Some(world())).obstacles {
+                    let half_size = obstacle.size / 2.0;
+                    assert!(world().contains(obstacle.pos - half_size));
+                    assert!(world().contains(obstacle.pos + half_size));
+
+                    let gap = (obstacle.pos.abs() - half_size).max(Vec2::ZERO);
+                    assert!(gap.length() > OBSTACLE_CLEAR_RADIUS, "{obstacle:?}");
+                }
+            }
+        }
+    }
+}